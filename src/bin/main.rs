@@ -1,9 +1,17 @@
 use config::Config;
+use libcrawl::reader::{export, ArchiveReader, ExportFormat, RecordFilter};
 use libcrawl::{crawl, crawl_utils, job_config};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
 
+const EXTRACT_USAGE: &str = "Usage: txtcrawl extract [--url REGEX] [--type TYPE]... [--lang CODE]... \
+[--since DATE] [--until DATE] [--format text|jsonl|dir] [--out DIR] FILE...";
+
 fn main() -> ExitCode {
+    let cmd_args: Vec<String> = std::env::args().collect();
+    if cmd_args.get(1).is_some_and(|arg| arg == "extract") {
+        return extract(&cmd_args[2..]);
+    }
     let job = match parse_config() {
         None => return ExitCode::FAILURE,
         Some(job) => job,
@@ -43,3 +51,87 @@ fn parse_config() -> Option<Config> {
     }
     Some(job)
 }
+
+/// `txtcrawl extract` : exports the records of WARC/WET files, conversion records by default
+fn extract(args: &[String]) -> ExitCode {
+    let (filter, format, files) = match parse_extract_args(args) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("{e}");
+            eprintln!("{EXTRACT_USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+    for file in files {
+        let reader = match ArchiveReader::open(&file, filter.clone()) {
+            Ok(reader) => reader,
+            Err(e) => {
+                eprintln!("{e}");
+                return ExitCode::FAILURE;
+            }
+        };
+        let mut failure = None;
+        let records = reader.map_while(|record| match record {
+            Ok(record) => Some(record),
+            Err(e) => {
+                failure = Some(e);
+                None
+            }
+        });
+        if let Err(e) = export(records, &format) {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+        if let Some(e) = failure {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+fn parse_extract_args(
+    args: &[String],
+) -> Result<(RecordFilter, ExportFormat, Vec<PathBuf>), String> {
+    let mut filter = RecordFilter::new();
+    let mut format = "text".to_string();
+    let mut out = None;
+    let mut has_type = false;
+    let mut files = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("Missing value for `{arg}`"))
+        };
+        match arg.as_str() {
+            "--url" => filter = filter.url(&value()?).map_err(|e| e.to_string())?,
+            "--type" => {
+                filter = filter.record_type(&value()?);
+                has_type = true;
+            }
+            "--lang" => filter = filter.language(&value()?),
+            "--since" => filter = filter.since(&value()?).map_err(|e| e.to_string())?,
+            "--until" => filter = filter.until(&value()?).map_err(|e| e.to_string())?,
+            "--format" => format = value()?,
+            "--out" => out = Some(PathBuf::from(value()?)),
+            _ if arg.starts_with("--") => return Err(format!("Unknown option `{arg}`")),
+            _ => files.push(PathBuf::from(arg)),
+        }
+    }
+    if files.is_empty() {
+        return Err("No input files".to_string());
+    }
+    if !has_type {
+        filter = filter.record_type("conversion");
+    }
+    let format = match (format.as_str(), out) {
+        ("text", _) => ExportFormat::Text,
+        ("jsonl", _) => ExportFormat::Jsonl,
+        ("dir", Some(out)) => ExportFormat::Directory(out),
+        ("dir", None) => return Err("`--format dir` needs `--out DIR`".to_string()),
+        (other, _) => return Err(format!("Unknown format `{other}`")),
+    };
+    Ok((filter, format, files))
+}
@@ -0,0 +1,232 @@
+use config::Config;
+use itertools::Itertools;
+use url::{Host, Url};
+
+/// Query parameters removed when the job does not set `canonical_strip_params`: campaign and
+/// click identifiers of analytics and advertising platforms. A trailing `*` matches any suffix
+pub const DEFAULT_STRIP_PARAMS: &[&str] = &[
+    "utm_*",
+    "gclid",
+    "gbraid",
+    "wbraid",
+    "dclid",
+    "fbclid",
+    "msclkid",
+    "yclid",
+    "twclid",
+    "igshid",
+    "mc_cid",
+    "mc_eid",
+    "_ga",
+    "_gl",
+    "_hsenc",
+    "_hsmi",
+    "mkt_tok",
+];
+
+/// Turns the URLs of a crawl into the keys they are deduplicated and indexed under, so that
+/// `HTTP://Example.com:80/a?utm_source=x#top` and `http://example.com/a` are the same page.
+/// The key is never requested, pages are fetched from the URL they were linked with
+#[derive(Clone, Debug, Default)]
+pub struct Canonicalizer {
+    strip_params: Vec<String>,
+    merge_schemes: bool,
+    merge_www: bool,
+}
+
+impl Canonicalizer {
+    pub fn new(strip_params: Vec<String>, merge_schemes: bool, merge_www: bool) -> Self {
+        Self {
+            strip_params: strip_params
+                .into_iter()
+                .map(|param| param.trim().to_lowercase())
+                .collect(),
+            merge_schemes,
+            merge_www,
+        }
+    }
+
+    /// Canonicalizer of the `canonical_strip_params`, `canonical_merge_schemes` and
+    /// `canonical_merge_www` job fields
+    pub fn from_job(job: &Config) -> Self {
+        Self::new(
+            job.get_array("canonical_strip_params")
+                .unwrap()
+                .into_iter()
+                .map(|value| value.into_string().unwrap())
+                .collect(),
+            job.get_bool("canonical_merge_schemes").unwrap(),
+            job.get_bool("canonical_merge_www").unwrap(),
+        )
+    }
+
+    /// Key of `url`: no fragment, lowercase scheme and host, no default port, dot segments
+    /// resolved, escapes of unreserved characters decoded and the others in uppercase, stripped
+    /// parameters removed and the remaining ones sorted. `https` becomes `http` with
+    /// `merge_schemes` and a leading `www.` is removed with `merge_www`.
+    /// A URL that cannot be parsed is its own key
+    pub fn canonicalize(&self, url: &str) -> String {
+        let Ok(mut url) = Url::parse(url) else {
+            return url.to_string();
+        };
+        url.set_fragment(None);
+        if self.merge_schemes && url.scheme() == "https" {
+            let _ = url.set_scheme("http");
+        }
+        if self.merge_www
+            && let Some(Host::Domain(domain)) = url.host()
+            && let Some(domain) = domain.strip_prefix("www.")
+            && !domain.is_empty()
+        {
+            let domain = domain.to_string();
+            let _ = url.set_host(Some(&domain));
+        }
+        let path = normalize_escapes(url.path());
+        url.set_path(&path);
+        let query = url.query().map(|query| {
+            query
+                .split('&')
+                .filter(|pair| !pair.is_empty())
+                .map(normalize_escapes)
+                .filter(|pair| !self.stripped(pair.split('=').next().unwrap_or_default()))
+                .sorted()
+                .join("&")
+        });
+        url.set_query(query.as_deref().filter(|query| !query.is_empty()));
+        url.to_string()
+    }
+
+    fn stripped(&self, name: &str) -> bool {
+        let name = name.to_lowercase();
+        self.strip_params
+            .iter()
+            .any(|param| match param.strip_suffix('*') {
+                Some(prefix) => name.starts_with(prefix),
+                None => name == *param,
+            })
+    }
+}
+
+/// Decodes `%XX` escapes of unreserved characters and uppercases the hex digits of the others
+fn normalize_escapes(part: &str) -> String {
+    let bytes = part.as_bytes();
+    let mut normalized = String::with_capacity(part.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| part.get(i + 1..i + 3))
+            .flatten()
+            .filter(|hex| hex.bytes().all(|digit| digit.is_ascii_hexdigit()))
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) => {
+                normalized.push(byte as char);
+                i += 3;
+            }
+            Some(byte) => {
+                normalized.push_str(&format!("%{byte:02X}"));
+                i += 3;
+            }
+            None => {
+                normalized.push(bytes[i] as char);
+                i += 1;
+            }
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn canonicalizer() -> Canonicalizer {
+        Canonicalizer::new(
+            DEFAULT_STRIP_PARAMS
+                .iter()
+                .map(|param| param.to_string())
+                .collect(),
+            false,
+            false,
+        )
+    }
+
+    #[test]
+    fn default_ports_and_fragments_are_dropped() {
+        let canonicalizer = canonicalizer();
+        assert_eq!(
+            canonicalizer.canonicalize("HTTP://Example.COM:80/a#top"),
+            "http://example.com/a"
+        );
+        assert_eq!(
+            canonicalizer.canonicalize("https://example.com:443/"),
+            "https://example.com/"
+        );
+        assert_eq!(
+            canonicalizer.canonicalize("http://example.com:8080/a"),
+            "http://example.com:8080/a"
+        );
+    }
+
+    #[test]
+    fn tracking_params_are_stripped() {
+        let canonicalizer = canonicalizer();
+        assert_eq!(
+            canonicalizer
+                .canonicalize("http://example.com/a?utm_source=x&UTM_Medium=y&id=3&gclid=z"),
+            "http://example.com/a?id=3"
+        );
+        assert_eq!(
+            canonicalizer.canonicalize("http://example.com/a?utm_campaign=x&fbclid=y"),
+            "http://example.com/a"
+        );
+        assert_eq!(
+            canonicalizer.canonicalize("http://example.com/a?utmost=1"),
+            "http://example.com/a?utmost=1"
+        );
+    }
+
+    #[test]
+    fn params_are_sorted() {
+        assert_eq!(
+            canonicalizer().canonicalize("http://example.com/?b=2&a=1&&a=0"),
+            "http://example.com/?a=0&a=1&b=2"
+        );
+    }
+
+    #[test]
+    fn escapes_are_normalized() {
+        let canonicalizer = canonicalizer();
+        assert_eq!(
+            canonicalizer.canonicalize("http://example.com/%7euser/a%2fb%41?q=%3d%2D"),
+            "http://example.com/~user/a%2FbA?q=%3D-"
+        );
+        assert_eq!(
+            canonicalizer.canonicalize("http://example.com/100%+1"),
+            "http://example.com/100%+1"
+        );
+    }
+
+    #[test]
+    fn dot_segments_are_resolved() {
+        assert_eq!(
+            canonicalizer().canonicalize("http://example.com/a/../b/./c?"),
+            "http://example.com/b/c"
+        );
+    }
+
+    #[test]
+    fn schemes_and_www_are_merged_on_request() {
+        let canonicalizer = Canonicalizer::new(Vec::new(), true, true);
+        assert_eq!(
+            canonicalizer.canonicalize("https://www.example.com/a"),
+            "http://example.com/a"
+        );
+        assert_eq!(canonicalizer.canonicalize("http://www./a"), "http://www./a");
+    }
+
+    #[test]
+    fn unparsable_urls_are_their_own_key() {
+        assert_eq!(canonicalizer().canonicalize("not a url"), "not a url");
+    }
+}
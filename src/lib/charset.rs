@@ -0,0 +1,125 @@
+use std::sync::OnceLock;
+
+use chardetng::EncodingDetector;
+use encoding_rs::Encoding;
+use regex::bytes::Regex;
+use url::{Host, Url};
+
+/// How many bytes of a page are searched for a `<meta>` charset declaration, as in the
+/// HTML prescan
+const META_PRESCAN: usize = 1024;
+
+/// Decodes a payload to text, choosing the charset from, in order : a byte order mark,
+/// the `Content-Type` header, a `<meta charset>` or `http-equiv` declaration and finally
+/// statistical detection. A declared charset the bytes are not valid in is ignored, since
+/// servers commonly label windows-1256 pages as UTF-8 or ISO-8859-6
+pub fn decode(body: &[u8], header_charset: Option<&str>, url: &str) -> (String, &'static Encoding) {
+    let encoding = Encoding::for_bom(body)
+        .map(|(encoding, _)| encoding)
+        .or_else(|| declared(body, header_charset))
+        .or_else(|| declared(body, meta_charset(body).as_deref()))
+        .unwrap_or_else(|| detect(body, url));
+    let (text, _, _) = encoding.decode(body);
+    (text.into_owned(), encoding)
+}
+
+/// Charset parameter of a `Content-Type` value
+pub fn content_type_charset(content_type: &str) -> Option<String> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        name.trim()
+            .eq_ignore_ascii_case("charset")
+            .then(|| value.trim().trim_matches(|c| c == '"' || c == '\'').to_string())
+    })
+}
+
+fn declared(body: &[u8], label: Option<&str>) -> Option<&'static Encoding> {
+    // without a BOM a UTF-16 label can not describe the page, HTML reads it as UTF-8 instead
+    let encoding = Encoding::for_label(label?.trim().as_bytes())?.output_encoding();
+    encoding
+        .decode_without_bom_handling_and_without_replacement(body)
+        .map(|_| encoding)
+}
+
+fn meta_charset(body: &[u8]) -> Option<String> {
+    static META: OnceLock<Regex> = OnceLock::new();
+    let meta = META.get_or_init(|| {
+        Regex::new(r#"(?i)<meta[^>]*?charset\s*=\s*["']?\s*([a-z0-9_:.\-]+)"#).unwrap()
+    });
+    let head = &body[..body.len().min(META_PRESCAN)];
+    meta.captures(head)
+        .map(|captures| String::from_utf8_lossy(&captures[1]).to_string())
+}
+
+/// Statistical guess, the top level domain of the page helps telling apart legacy encodings
+fn detect(body: &[u8], url: &str) -> &'static Encoding {
+    let tld = Url::parse(url).ok().and_then(|url| match url.host() {
+        Some(Host::Domain(domain)) => domain
+            .rsplit('.')
+            .next()
+            .filter(|tld| tld.is_ascii() && !tld.is_empty())
+            .map(|tld| tld.to_lowercase()),
+        _ => None,
+    });
+    let mut detector = EncodingDetector::new();
+    detector.feed(body, true);
+    detector.guess(tld.as_deref().map(str::as_bytes), true)
+}
+
+#[cfg(test)]
+mod tests {
+    use encoding_rs::{UTF_8, WINDOWS_1252, WINDOWS_1256};
+
+    use super::*;
+
+    const URL: &str = "http://example.com/";
+
+    #[test]
+    fn bom_wins_over_header_and_meta() {
+        let body = b"\xef\xbb\xbf<meta charset=\"windows-1256\"><p>caf\xc3\xa9</p>";
+        let (text, encoding) = decode(body, Some("windows-1252"), URL);
+        assert_eq!(encoding, UTF_8);
+        assert_eq!(text, "<meta charset=\"windows-1256\"><p>caf\u{e9}</p>");
+    }
+
+    #[test]
+    fn header_wins_over_meta() {
+        let body = b"<meta charset=\"windows-1256\"><p>caf\xc3\xa9</p>";
+        let (text, encoding) = decode(body, Some("utf-8"), URL);
+        assert_eq!(encoding, UTF_8);
+        assert!(text.ends_with("<p>caf\u{e9}</p>"));
+    }
+
+    #[test]
+    fn meta_is_used_without_header() {
+        let body =
+            b"<meta http-equiv=\"Content-Type\" content=\"text/html; charset=windows-1256\">\
+                     <p>\xc7\xe1\xda\xd1\xc8\xed\xc9</p>";
+        let (text, encoding) = decode(body, None, URL);
+        assert_eq!(encoding, WINDOWS_1256);
+        assert!(text.contains("\u{627}\u{644}\u{639}\u{631}\u{628}\u{64a}\u{629}"));
+    }
+
+    #[test]
+    fn header_the_bytes_are_invalid_in_is_ignored() {
+        let body = b"<meta charset='iso-8859-1'><p>caf\xe9</p>";
+        let (text, encoding) = decode(body, Some("utf-8"), URL);
+        assert_eq!(encoding, WINDOWS_1252);
+        assert!(text.ends_with("<p>caf\u{e9}</p>"));
+    }
+
+    #[test]
+    fn utf16_label_without_bom_reads_as_utf8() {
+        let (_, encoding) = decode(b"<p>plain</p>", Some("utf-16le"), URL);
+        assert_eq!(encoding, UTF_8);
+    }
+
+    #[test]
+    fn charset_parameter_of_content_type() {
+        assert_eq!(
+            content_type_charset("text/html; Charset=\"ISO-8859-6\""),
+            Some("ISO-8859-6".to_string())
+        );
+        assert_eq!(content_type_charset("text/html"), None);
+    }
+}
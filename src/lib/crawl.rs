@@ -13,22 +13,111 @@ use async_channel::Sender as AsyncSender;
 use colored::Colorize;
 use config::Config;
 use futures::future::join_all;
-use libflate::gzip::Encoder;
-use reqwest::{Client, Error};
+use itertools::Itertools;
+use reqwest::header::{
+    HeaderValue, ACCEPT, ACCEPT_ENCODING, LOCATION, USER_AGENT as USER_AGENT_HEADER,
+};
+use reqwest::redirect::Policy;
+use reqwest::{Client, Error, Request};
 use url::Url;
-use warc::WarcWriter;
-use whatlang::{Detector, Lang};
+use warc::WarcHeader;
 
-use crate::{crawl_utils, CrawlCounters, CrawlEntry, lang, ScrapEntry};
+use crate::{sink, CrawlCounters, CrawlEntry, lang, links, ScrapEntry};
+use crate::canonical::Canonicalizer;
 use crate::crawl_utils::disperse_domains;
-use crate::lang::has_language;
-use crate::response::{Response, WetRecord};
-use crate::robots::{Robots, RobotsVerdict};
+use crate::digest::{add_digests, DigestAlgorithm};
+use crate::document::DocumentKind;
+use crate::extract::TextExtractor;
+use crate::lang::{DetectorBackend, LanguageDetector, LanguagePolicy, LanguageProfile};
+use crate::links::{LinkSource, PageLink};
+use crate::mime::MimePolicy;
+use crate::quality::{QualityFilters, QUALITY_HEADER};
+use crate::response::{
+    Response, ResponseError, WetRecord, LANGUAGE_CONFIDENCE_HEADER, LANGUAGE_DISTRIBUTION_HEADER,
+    LANGUAGE_HEADER,
+};
+use crate::robots::{Robots, RobotsPolicy, RobotsVerdict};
+use crate::scope::CrawlScope;
+use crate::sink::RecordSink;
 
-type WetFile = WarcWriter<BufWriter<Encoder<File>>>;
+const USER_AGENT: &str = concat!("txtcrawl/", env!("CARGO_PKG_VERSION"));
+
+/// Redirects `fetch` follows before taking the response as it is, as many as reqwest would
+const MAX_REDIRECTS: usize = 10;
+
+/// What is archived for every fetched page
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveMode {
+    /// only the extracted text as a `conversion` record
+    Text,
+    /// `response` and `request` records of the capture, followed by the text `conversion`
+    Full,
+}
+
+impl ArchiveMode {
+    pub fn from_name(mode: &str) -> Option<Self> {
+        match mode.to_lowercase().as_str() {
+            "text" => Some(Self::Text),
+            "full" => Some(Self::Full),
+            _ => None,
+        }
+    }
+}
+
+/// What is archived of pages outside `accept_languages`
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LanguageArchiving {
+    /// every page goes to the job outputs
+    All,
+    /// pages outside `accept_languages` are not archived
+    Accepted,
+    /// pages outside `accept_languages` go to separate outputs named after `<prefix>-rejects`
+    Separate,
+}
+
+impl LanguageArchiving {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "all" => Some(Self::All),
+            "accepted" => Some(Self::Accepted),
+            "separate" => Some(Self::Separate),
+            _ => None,
+        }
+    }
+}
 
 pub fn start_crawl(seeds: Vec<CrawlEntry>, job: &Config) {
-    let (warc_dst, crawler_count, link_timeout, accept_langs, respect_robots) = (
+    start_crawl_with_sinks(seeds, job, Vec::new())
+}
+
+/// Crawls like `start_crawl`, writing records to `extra_sinks` as well as to the sinks
+/// selected in the job
+pub fn start_crawl_with_sinks(
+    seeds: Vec<CrawlEntry>,
+    job: &Config,
+    extra_sinks: Vec<Box<dyn RecordSink>>,
+) {
+    let (
+        warc_dst,
+        crawler_count,
+        link_timeout,
+        accept_langs,
+        mut model_langs,
+        language_min_share,
+        language_min_confidence,
+        detector_backend,
+        language_archiving,
+        respect_robots,
+        archive_mode,
+        digest_algorithm,
+        text_extractor,
+        mime_policy,
+        quality_filters,
+        link_sources,
+        canonicalizer,
+        robots_policy,
+        scope,
+    ) = (
         job.get_string("destination_warc").unwrap(),
         job.get_int("crawl_tasks").unwrap() as usize,
         job.get_int("link_timeout").unwrap() as u64,
@@ -37,9 +126,47 @@ pub fn start_crawl(seeds: Vec<CrawlEntry>, job: &Config) {
             .into_iter()
             .map(|value| value.into_string().unwrap())
             .collect::<Vec<String>>(),
+        job.get_array("detect_languages")
+            .unwrap()
+            .into_iter()
+            .map(|value| value.into_string().unwrap())
+            .collect::<Vec<String>>(),
+        job.get_float("language_min_share").unwrap(),
+        job.get_float("language_min_confidence").unwrap(),
+        DetectorBackend::from_name(&job.get_string("language_detector").unwrap()).unwrap(),
+        LanguageArchiving::from_name(&job.get_string("archive_languages").unwrap()).unwrap(),
         job.get_bool("respect_robots").unwrap(),
+        ArchiveMode::from_name(&job.get_string("archive_mode").unwrap()).unwrap(),
+        DigestAlgorithm::from_name(&job.get_string("warc_digest").unwrap()).unwrap(),
+        TextExtractor::from_name(&job.get_string("text_extractor").unwrap()).unwrap(),
+        MimePolicy::new(
+            job.get_array("mime_allow")
+                .unwrap()
+                .into_iter()
+                .map(|value| value.into_string().unwrap())
+                .collect(),
+            job.get_array("mime_deny")
+                .unwrap()
+                .into_iter()
+                .map(|value| value.into_string().unwrap())
+                .collect(),
+        ),
+        QualityFilters::from_job(job),
+        job.get_array("link_sources")
+            .unwrap()
+            .into_iter()
+            .map(|value| LinkSource::from_name(&value.into_string().unwrap()).unwrap())
+            .collect::<Vec<LinkSource>>(),
+        Canonicalizer::from_job(job),
+        RobotsPolicy::from_job(job),
+        CrawlScope::from_job(job, &seeds),
     );
-    let wet_file = WarcWriter::from_path_gzip(&warc_dst).unwrap();
+    let mut sinks = sink::job_sinks(job).unwrap();
+    sinks.extend(extra_sinks);
+    let reject_sinks = match language_archiving {
+        LanguageArchiving::Separate => sink::reject_sinks(job).unwrap(),
+        _ => Vec::new(),
+    };
     let bad_urls_log = BufWriter::new(
         File::options()
             .read(true)
@@ -52,40 +179,65 @@ pub fn start_crawl(seeds: Vec<CrawlEntry>, job: &Config) {
     let (tx_processor, rx_crawler) = async_channel::unbounded();
     let (tx_crawler, rx_processor) = std_channel::<ScrapEntry>();
     let (tx_crawl_log,rx_logger) = std_channel::<String>();
-    let model_langs = vec!["arabic", "english"];
-    let accept_langs = lang::lang_builder(accept_langs.iter().map(|lang| lang.as_str()).collect());
-    let lang_detector = lang::build_langdetector(model_langs);
+    // accepted languages must be among those the detector can answer with
+    if !model_langs.is_empty() {
+        model_langs.extend(accept_langs.iter().cloned());
+    }
+    let language_policy = LanguagePolicy::new(
+        lang::lang_builder(accept_langs.iter().map(|lang| lang.as_str()).collect()),
+        language_min_share,
+        language_min_confidence,
+    );
+    let processor = PageProcessor {
+        lang_detector: lang::build_langdetector(
+            detector_backend,
+            model_langs.iter().map(|lang| lang.as_str()).collect(),
+        ),
+        language_policy,
+        archive_mode,
+        text_extractor,
+        quality_filters,
+        link_sources,
+        robots_policy,
+    };
     let rt = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()
         .unwrap();
     let counters = Arc::new(CrawlCounters::default());
+    // pages are deduplicated on their canonical form, they are still fetched from the URL found
+    let mut known_urls: AHashSet<String> = AHashSet::new();
+    let mut out_of_scope: AHashSet<String> = AHashSet::new();
+    let seeds: Vec<CrawlEntry> = seeds
+        .into_iter()
+        .filter(|entry| known_urls.insert(canonicalizer.canonicalize(&entry.url)))
+        .collect();
     counters.add_to("queued", seeds.len() as u64);
-    let mut known_urls: AHashSet<String> = seeds.iter().map(|entry| entry.url.clone()).collect();
     for x in seeds {
         tx_processor.send_blocking(x).unwrap();
     }
     let started_crawling = Arc::new(AtomicBool::new(false));
     let mut crawlers = Vec::with_capacity(crawler_count);
-    let client: reqwest::Client = reqwest::Client::builder()
-        .connect_timeout(Duration::from_millis(link_timeout))
-        .build()
-        .unwrap();
-    let robots = Arc::new(Robots::new());
+    let client = build_client(Duration::from_millis(link_timeout));
+    let context = CrawlContext {
+        client,
+        counters: counters.clone(),
+        started_crawling: started_crawling.clone(),
+        robots: Arc::new(Robots::new()),
+        respect_robots,
+        tx_crawl_log,
+        mime_policy: Arc::new(mime_policy),
+    };
     for _ in 0..crawler_count {
         crawlers.push(crawl_url(
-            client.clone(),
+            context.clone(),
             rx_crawler.clone(),
             tx_crawler.clone(),
-            counters.clone(),
-            started_crawling.clone(),
-            robots.clone(),
             tx_processor.clone(),
-            respect_robots,
-            tx_crawl_log.clone()
         ));
     }
-    let accept_all = accept_langs.is_empty();
+    // the logger stops once the crawlers have dropped their log senders
+    drop(context);
     let counters2 = counters.clone();
     rt.spawn_blocking(move || {
         let mut link_cache = Vec::new();
@@ -95,28 +247,43 @@ pub fn start_crawl(seeds: Vec<CrawlEntry>, job: &Config) {
         loop {
             match &rx_processor.recv_timeout(Duration::from_secs(60)) {
                 Ok(crawled) => {
-                    let out = process_crawled(crawled, &lang_detector, &accept_langs, accept_all);
-                    tx_processor_writer.send(out.0).unwrap();
-                    if let Some(mut links) = out.1 {
+                    let (records, outlinks, accepted) = processor.process(crawled);
+                    match (accepted, language_archiving) {
+                        // pages opted out of archiving leave nothing to write
+                        _ if records.is_empty() => {}
+                        (true, _) | (false, LanguageArchiving::All) => {
+                            tx_processor_writer.send((records, false)).unwrap()
+                        }
+                        (false, LanguageArchiving::Accepted) => {}
+                        (false, LanguageArchiving::Separate) => {
+                            tx_processor_writer.send((records, true)).unwrap()
+                        }
+                    }
+                    if let Some(mut links) = outlinks {
                         links.retain(|i| {
-                            url::Url::parse(&(i.url)).is_ok_and(|url| !url.cannot_be_a_base() && ["http", "https"].contains(&url.scheme()))
-                                && known_urls.insert(i.url.clone())
+                            let key = canonicalizer.canonicalize(&i.url);
+                            match scope.check(&i.url) {
+                                Ok(()) => known_urls.insert(key),
+                                Err(reason) => {
+                                    // counted once per URL, however many pages link to it
+                                    if out_of_scope.insert(key) {
+                                        counters2.increment_out_of_scope(reason);
+                                    }
+                                    false
+                                }
+                            }
                         });
+                        link_cache.append(&mut links);
                         if tx_processor.is_empty() || link_cache.len() >= 400 {
-                            let cached = mem::take(&mut link_cache);
-                            let dispersed = disperse_domains(cached);
-                            counters2.add_to("extra", dispersed.len() as u64);
-                            counters2.add_to("queued", dispersed.len() as u64);
-                            for x in dispersed {
-                                tx_processor.send_blocking(x).unwrap()
-                            }
-                        } else {
-                            link_cache.append(&mut links)
+                            enqueue(mem::take(&mut link_cache), &tx_processor, &counters2);
                         }
                     }
                 }
                 Err(_) => {
-                    if tx_processor.is_empty() {
+                    // links of the last pages are still cached when the queue ran dry meanwhile
+                    if !link_cache.is_empty() {
+                        enqueue(mem::take(&mut link_cache), &tx_processor, &counters2);
+                    } else if tx_processor.is_empty() {
                         break;
                     } else {
                         continue;
@@ -124,8 +291,12 @@ pub fn start_crawl(seeds: Vec<CrawlEntry>, job: &Config) {
                 }
             }
         }
+        // crawlers keep senders to loop entries back, closing the queue lets them return so the
+        // writer can close the last output file and write its indexes
+        tx_processor.close();
     });
-    rt.spawn_blocking(|| background_writer(rx_bgwriter, wet_file));
+    let counters4 = counters.clone();
+    rt.spawn_blocking(move || background_writer(rx_bgwriter, sinks, reject_sinks, digest_algorithm, counters4));
     rt.spawn_blocking(|| log(rx_logger,bad_urls_log));
     let counters3 = counters.clone();
     rt.spawn(async move {
@@ -138,20 +309,50 @@ pub fn start_crawl(seeds: Vec<CrawlEntry>, job: &Config) {
     println!("{}", counters.to_string().blue());
 }
 
-async fn crawl_url(
+/// Queues newly found links, spread so consecutive entries are on different domains
+fn enqueue(links: Vec<CrawlEntry>, queue: &AsyncSender<CrawlEntry>, counters: &CrawlCounters) {
+    let dispersed = disperse_domains(links);
+    counters.add_to("extra", dispersed.len() as u64);
+    counters.add_to("queued", dispersed.len() as u64);
+    for x in dispersed {
+        queue.send_blocking(x).unwrap()
+    }
+}
+
+/// What the crawl tasks share
+#[derive(Clone)]
+struct CrawlContext {
     client: Client,
-    rx_url: AsyncReceiver<CrawlEntry>,
-    tx_page: Sender<ScrapEntry>,
     counters: Arc<CrawlCounters>,
     started_crawling: Arc<AtomicBool>,
     robots: Arc<Robots>,
-    loopback: AsyncSender<CrawlEntry>,
     respect_robots: bool,
-    tx_crawl_log : Sender<String>
+    tx_crawl_log: Sender<String>,
+    mime_policy: Arc<MimePolicy>,
+}
+
+async fn crawl_url(
+    context: CrawlContext,
+    rx_url: AsyncReceiver<CrawlEntry>,
+    tx_page: Sender<ScrapEntry>,
+    loopback: AsyncSender<CrawlEntry>,
 ) {
+    let CrawlContext {
+        client,
+        counters,
+        started_crawling,
+        robots,
+        respect_robots,
+        tx_crawl_log,
+        mime_policy,
+    } = context;
     started_crawling.store(true, Ordering::Relaxed);
     while let Ok(crawl_entry) = rx_url.recv().await {
         counters.decrement_queued();
+        if let Err(reason) = mime_policy.check_url(&crawl_entry.url) {
+            skip(&crawl_entry.url, &reason, &counters, &tx_crawl_log);
+            continue;
+        }
         /*
         ask if url is valid,
         if yes : ask if domain robots has been saved , if yes : ask if can visit again
@@ -167,24 +368,36 @@ async fn crawl_url(
                 Some(verdict) => match verdict {
                     RobotsVerdict::ForbiddenPath => continue,
                     RobotsVerdict::CrawlDelay => {
-                        loopback.send(crawl_entry).await.unwrap();
+                        // the queue is closed once the processor has nothing left to queue, an
+                        // entry still waiting for its delay then counts as failed
+                        if let Err(error) = loopback.send(crawl_entry).await {
+                            let url = error.into_inner().url;
+                            eprintln!("{}", format!("{url} failed : queue closed").red());
+                            counters.increment_failed();
+                            tx_crawl_log.send(url).unwrap();
+                        }
                         continue;
                     }
                     RobotsVerdict::Proceed => robots.update_domain(&domain.unwrap()).await,
                 },
             }
         }
-        let resp = client.get(&crawl_entry.url).send().await;
+        // the request is kept so the headers that were sent can be archived
+        let resp = fetch(&client, &crawl_entry.url).await;
         let response = match resp {
-            Ok(resp) => match Response::from_request(resp).await {
+            Ok((resp, request)) => match Response::from_request(resp, &request, &mime_policy).await {
                 Ok(resp) => Some(resp),
+                Err(ResponseError::Skipped(reason)) => {
+                    skip(&crawl_entry.url, &reason, &counters, &tx_crawl_log);
+                    continue;
+                }
                 Err(e) => {
-                    eprintln!("{}", format!("{} failed", &crawl_entry.url).red());
+                    eprintln!("{}", format!("{} failed", crawl_entry.url).red());
                     None
                 }
             },
             Err(e) => {
-                eprintln!("{}", format!("{} failed", &crawl_entry.url).red());
+                eprintln!("{}", format!("{} failed", crawl_entry.url).red());
                 None
             }
         };
@@ -205,6 +418,59 @@ async fn crawl_url(
     }
 }
 
+/// Counts and logs a URL left out by the content type policy
+fn skip(url: &str, reason: &str, counters: &CrawlCounters, tx_crawl_log: &Sender<String>) {
+    eprintln!("{}", format!("{url} skipped : {reason}").yellow());
+    counters.increment_skipped();
+    tx_crawl_log.send(format!("{url} skipped : {reason}")).unwrap();
+}
+
+/// Client of the crawl. Payloads are archived exactly as sent, `Response` removes the content
+/// encoding itself, and redirects are followed by `fetch`
+fn build_client(connect_timeout: Duration) -> Client {
+    Client::builder()
+        .connect_timeout(connect_timeout)
+        .no_gzip()
+        .redirect(Policy::none())
+        .build()
+        .unwrap()
+}
+
+/// Sends a GET for `url` and follows its redirects, up to `MAX_REDIRECTS`, with a new request
+/// for every hop so each one carries the headers of its own URL. Returns the last response and
+/// the request it answers
+async fn fetch(client: &Client, url: &str) -> reqwest::Result<(reqwest::Response, Request)> {
+    let mut request = build_request(client, url)?;
+    let mut redirects = 0;
+    loop {
+        let response = client.execute(request.try_clone().unwrap()).await?;
+        let location = response
+            .headers()
+            .get(LOCATION)
+            .filter(|_| response.status().is_redirection())
+            .and_then(|location| location.to_str().ok())
+            .and_then(|location| response.url().join(location).ok());
+        match location {
+            Some(next) if redirects < MAX_REDIRECTS => {
+                redirects += 1;
+                request = build_request(client, next.as_str())?;
+            }
+            _ => return Ok((response, request)),
+        }
+    }
+}
+
+/// Sets the request headers explicitly instead of relying on client defaults, so they appear in
+/// the archived `request` record. `Host` is left to hyper, which derives it from the URL
+fn build_request(client: &Client, url: &str) -> reqwest::Result<Request> {
+    let mut request = client.get(url).build()?;
+    let headers = request.headers_mut();
+    headers.insert(USER_AGENT_HEADER, HeaderValue::from_static(USER_AGENT));
+    headers.insert(ACCEPT, HeaderValue::from_static("*/*"));
+    headers.insert(ACCEPT_ENCODING, HeaderValue::from_static("gzip"));
+    Ok(request)
+}
+
 #[inline(always)]
 async fn eval_robots(
     client: &Client,
@@ -233,43 +499,181 @@ async fn eval_robots(
 }
 
 async fn url_to_text(client: &Client, url: &str) -> Result<String, Error> {
-    let response = client.get(url).send().await?;
+    let (response, _) = fetch(client, url).await?;
     response.text().await
 }
 
-fn process_crawled(
-    response: &ScrapEntry,
-    lang_detector: &Detector,
-    accept_langs: &[Lang],
-    accept_all: bool,
-) -> (WetRecord, Option<Vec<CrawlEntry>>) {
-    let soup = response.response.to_soup();
-    let wet_record = response.response.to_warcrecord(Some(&soup));
-    let outlinks = if response.crawl_depth != 0
-        && (accept_all || has_language(lang_detector, &wet_record.body, accept_langs))
-    {
-        Some(
-            crawl_utils::soup_links(&soup, &[])
+/// Job settings deciding what is archived of every fetched page and which of its links are
+/// followed
+struct PageProcessor {
+    lang_detector: Box<dyn LanguageDetector>,
+    language_policy: LanguagePolicy,
+    archive_mode: ArchiveMode,
+    text_extractor: TextExtractor,
+    quality_filters: QualityFilters,
+    link_sources: Vec<LinkSource>,
+    robots_policy: RobotsPolicy,
+}
+
+impl PageProcessor {
+    /// Records to write for `response`, the links to follow and whether its languages are accepted
+    fn process(&self, response: &ScrapEntry) -> (Vec<WetRecord>, Option<Vec<CrawlEntry>>, bool) {
+        let kind = response.response.document_kind();
+        let soup = (kind == DocumentKind::Html).then(|| response.response.to_soup());
+        let text = match &soup {
+            Some(soup) => Some(response.response.text(Some(soup), self.text_extractor)),
+            None => response.response.document_text(kind).ok(),
+        };
+        let languages =
+            LanguageProfile::detect(self.lang_detector.as_ref(), text.as_deref().unwrap_or_default());
+        let accepted = self.language_policy.accepts(&languages);
+        let quality = self.quality_filters.evaluate(text.as_deref().unwrap_or_default());
+        let conversion_record = |refers_to: Option<&[u8]>| {
+            if quality.drop {
+                return None;
+            }
+            let mut record = match &soup {
+                Some(soup) => Some(
+                    response
+                        .response
+                        .to_warcrecord(Some(soup), self.text_extractor, refers_to),
+                ),
+                None => text
+                    .clone()
+                    .map(|text| response.response.to_document_record(kind, text, refers_to)),
+            };
+            if let Some(record) = record.as_mut() {
+                add_language_headers(record, &languages);
+                if !quality.flags.is_empty() {
+                    record.headers.headers.insert(
+                        WarcHeader::Unknown(QUALITY_HEADER.to_string()),
+                        quality.flags.join(",").into_bytes(),
+                    );
+                }
+            }
+            record
+        };
+        let directives = response.response.robots_directives(soup.as_ref());
+        let links = match (&soup, kind) {
+            _ if !self.robots_policy.follows(&directives) => vec![],
+            (Some(soup), _) => links::page_links(soup, response.response.url(), LinkSource::ALL),
+            (None, DocumentKind::Pdf) => response
+                .response
+                .pdf_links()
                 .into_iter()
-                .map(|link| CrawlEntry {
-                    url: link,
-                    crawl_depth: response.crawl_depth,
-                })
-                .collect::<Vec<CrawlEntry>>(),
-        )
-    } else {
-        None
-    };
-    (wet_record, outlinks)
+                .map(|link| PageLink::new(link, LinkSource::Pdf))
+                .collect(),
+            (None, _) => vec![],
+        };
+        let records = match self.archive_mode {
+            _ if !self.robots_policy.archives(&directives) => vec![],
+            ArchiveMode::Text => {
+                let mut records: Vec<WetRecord> = conversion_record(None).into_iter().collect();
+                // without a capture the outlinks are attached to the text
+                if let Some(conversion_id) = records.first().map(WetRecord::record_id)
+                    && !links.is_empty()
+                {
+                    records.push(response.response.to_metadata_record(&conversion_id, &links));
+                }
+                records
+            }
+            ArchiveMode::Full => {
+                let response_record = response.response.to_response_record();
+                let response_id = response_record.record_id();
+                let request_record = response.response.to_request_record(&response_id);
+                let mut records = vec![response_record, request_record];
+                if !links.is_empty() {
+                    records.push(response.response.to_metadata_record(&response_id, &links));
+                }
+                records.extend(conversion_record(Some(&response_id)));
+                records
+            }
+        };
+        let outlinks = if response.crawl_depth != 0 && accepted && !quality.drop && !quality.nofollow
+        {
+            Some(
+                links
+                    .into_iter()
+                    .filter(|link| {
+                        self.link_sources.contains(&link.source)
+                            && self.robots_policy.follows_link(link)
+                    })
+                    .map(|link| CrawlEntry {
+                        url: link.url,
+                        crawl_depth: response.crawl_depth,
+                    })
+                    .collect::<Vec<CrawlEntry>>(),
+            )
+        } else {
+            None
+        };
+        (records, outlinks, accepted)
+    }
 }
-fn background_writer(records: Receiver<WetRecord>, mut warc: WetFile) {
-    while let Ok(rec) = records.recv() {
-        warc.write_raw(rec.headers, &rec.body).unwrap();
+
+/// Languages identified in the text of a conversion record, left out when none was
+fn add_language_headers(record: &mut WetRecord, languages: &LanguageProfile) {
+    let distribution = languages.distribution();
+    if distribution.is_empty() {
+        return;
+    }
+    let headers = [
+        (
+            LANGUAGE_HEADER,
+            distribution.iter().map(|(lang, _)| lang.code()).join(","),
+        ),
+        (
+            LANGUAGE_DISTRIBUTION_HEADER,
+            distribution
+                .iter()
+                .map(|(lang, share)| format!("{}:{share:.3}", lang.code()))
+                .join(","),
+        ),
+        (
+            LANGUAGE_CONFIDENCE_HEADER,
+            format!("{:.3}", languages.confidence()),
+        ),
+    ];
+    for (name, value) in headers {
+        record
+            .headers
+            .headers
+            .insert(WarcHeader::Unknown(name.to_string()), value.into_bytes());
+    }
+}
+
+/// Writes every batch to `sinks`, or to `reject_sinks` when it is flagged as off-language
+/// Writes every batch to each sink. A sink that fails is reported and counted, it does not stop
+/// the other sinks from getting the batch or the crawl from going on
+fn background_writer(
+    records: Receiver<(Vec<WetRecord>, bool)>,
+    mut sinks: Vec<Box<dyn RecordSink>>,
+    mut reject_sinks: Vec<Box<dyn RecordSink>>,
+    digest_algorithm: DigestAlgorithm,
+    counters: Arc<CrawlCounters>,
+) {
+    while let Ok((mut batch, rejected)) = records.recv() {
+        for rec in batch.iter_mut() {
+            add_digests(rec, digest_algorithm);
+        }
+        let sinks = if rejected { &mut reject_sinks } else { &mut sinks };
+        for sink in sinks.iter_mut() {
+            if let Err(e) = sink.write_batch(&batch) {
+                let url = batch
+                    .first()
+                    .and_then(|record| record.header(WarcHeader::TargetURI))
+                    .unwrap_or_default();
+                eprintln!("{}", format!("{url} not written : {e}").red());
+                counters.increment_write_errors();
+            }
+        }
     }
     println!("finish");
-    unsafe {
-        let gzip_stream = warc.into_inner().unwrap_unchecked();
-        gzip_stream.finish().unwrap();
+    for sink in sinks.iter_mut().chain(reject_sinks.iter_mut()) {
+        if let Err(e) = sink.finish() {
+            eprintln!("{}", format!("finishing a sink failed : {e}").red());
+            counters.increment_write_errors();
+        }
     }
 }
 
@@ -283,3 +687,63 @@ fn log(rx:Receiver<String>, mut log:BufWriter<File>){
     }
     log.flush().unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Answers one connection with `respond(request head)`
+    async fn serve_once(listener: TcpListener, respond: impl FnOnce(&str) -> String) {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut head = Vec::new();
+        let mut buf = [0; 1024];
+        while !head.ends_with(b"\r\n\r\n") {
+            let n = stream.read(&mut buf).await.unwrap();
+            head.extend_from_slice(&buf[..n]);
+        }
+        let response = respond(&String::from_utf8_lossy(&head));
+        stream.write_all(response.as_bytes()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn cross_host_redirects_are_sent_to_the_new_host() {
+        let origin = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let target = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let origin_addr = origin.local_addr().unwrap();
+        let target_addr = target.local_addr().unwrap();
+        let target_host = format!("host: {target_addr}\r\n");
+        tokio::spawn(serve_once(origin, move |_| {
+            format!(
+                "HTTP/1.1 302 Found\r\nlocation: http://{target_addr}/target\r\ncontent-length: 0\r\n\r\n"
+            )
+        }));
+        tokio::spawn(serve_once(target, move |head| {
+            if head.to_ascii_lowercase().contains(&target_host) {
+                "HTTP/1.1 200 OK\r\ncontent-type: text/plain\r\ncontent-length: 2\r\n\r\nok"
+                    .to_string()
+            } else {
+                "HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\n\r\n".to_string()
+            }
+        }));
+
+        let client = build_client(Duration::from_secs(5));
+        let (resp, request) = fetch(&client, &format!("http://{origin_addr}/start"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+        assert_eq!(
+            request.url().as_str(),
+            format!("http://{target_addr}/target")
+        );
+
+        let response = Response::from_request(resp, &request, &MimePolicy::default())
+            .await
+            .unwrap();
+        let record = response.to_request_record(b"<urn:uuid:0>");
+        let body = String::from_utf8(record.body).unwrap();
+        assert!(body.starts_with(&format!("GET /target HTTP/1.1\r\nhost: {target_addr}\r\n")));
+        assert!(body.ends_with("\r\n\r\n"));
+    }
+}
@@ -1,5 +1,5 @@
 use crate::robots::Robots;
-use crate::CrawlEntry;
+use crate::{extract, CrawlEntry};
 use ahash::AHashMap;
 use itertools::Itertools;
 use reqwest::header::HeaderMap;
@@ -12,16 +12,16 @@ use std::hash::Hash;
 use std::path::Path;
 use url::Url;
 
-pub fn http_headers_fmt(header_map: &HeaderMap) -> String {
-    let mut displayed = String::new();
+/// Header block as sent on the wire, values are copied byte for byte since they need not be ASCII
+pub fn http_headers_fmt(header_map: &HeaderMap) -> Vec<u8> {
+    let mut displayed = Vec::new();
     for (k, v) in header_map {
-        displayed.push_str(k.to_owned().as_ref());
-        displayed.push_str(": ");
-        displayed.push_str(v.to_str().unwrap_or_default());
-        displayed.push_str("\r\n");
+        displayed.extend_from_slice(k.as_str().as_bytes());
+        displayed.extend_from_slice(b": ");
+        displayed.extend_from_slice(v.as_bytes());
+        displayed.extend_from_slice(b"\r\n");
     }
-    displayed.pop();
-    displayed.pop();
+    displayed.truncate(displayed.len().saturating_sub(2));
     displayed
 }
 
@@ -43,28 +43,16 @@ pub fn file_lines(file: &Path) -> Vec<String> {
         .collect()
 }
 
-pub fn soup_links(soup: &Soup, protocols: &[String]) -> Vec<String> {
-    let mut links = vec![];
-    for link in soup.tag("a").find_all() {
-        if let Some(href) = link.get("href") {
-            if !protocols.is_empty() {
-                for protocol in protocols {
-                    if href.starts_with(protocol) {
-                        links.push(href);
-                        break;
-                    }
-                }
-            } else {
-                links.push(href);
-            }
-        }
-    }
-    links
+/// Text of the `<title>` element with its whitespace collapsed, `None` when missing or empty
+pub fn soup_title(soup: &Soup) -> Option<String> {
+    let title = soup.tag("title").find()?.text();
+    let title = title.split_whitespace().join(" ");
+    (!title.is_empty()).then_some(title)
 }
 
 #[inline]
 pub fn soup_text(soup: &Soup) -> String {
-    soup.text()
+    extract::visible_text(soup)
 }
 
 pub fn init_seed_list(file: &Path, recursion_depth: u8) -> Vec<CrawlEntry> {
@@ -0,0 +1,123 @@
+use data_encoding::BASE32;
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+use warc::{RecordType, WarcHeader};
+
+use crate::response::WetRecord;
+
+/// Hash used for `WARC-Block-Digest` and `WARC-Payload-Digest`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DigestAlgorithm {
+    Sha1,
+    Sha256,
+}
+
+impl DigestAlgorithm {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "sha1" | "sha-1" => Some(Self::Sha1),
+            "sha256" | "sha-256" => Some(Self::Sha256),
+            _ => None,
+        }
+    }
+
+    /// Labelled base32 digest such as `sha1:3I42H3S6NNFQ2MSVX7XZKYAYSCX5QBYJ`
+    pub fn digest(&self, data: &[u8]) -> String {
+        match self {
+            Self::Sha1 => format!("sha1:{}", BASE32.encode(&Sha1::digest(data))),
+            Self::Sha256 => format!("sha256:{}", BASE32.encode(&Sha256::digest(data))),
+        }
+    }
+}
+
+/// Adds the block digest to `record`, and the payload digest when its block has a payload:
+/// the entity body of an HTTP response, or the whole block of a conversion or resource
+pub fn add_digests(record: &mut WetRecord, algorithm: DigestAlgorithm) {
+    let block_digest = algorithm.digest(&record.body);
+    let record_type = record
+        .header(WarcHeader::WarcType)
+        .map(RecordType::from)
+        .unwrap_or_else(|| RecordType::Unknown(String::new()));
+    let payload_digest = match record_type {
+        RecordType::Response => http_payload(&record.body).map(|payload| algorithm.digest(payload)),
+        RecordType::Conversion | RecordType::Resource => Some(block_digest.clone()),
+        _ => None,
+    };
+    let headers = &mut record.headers.headers;
+    headers.insert(WarcHeader::BlockDigest, block_digest.into_bytes());
+    if let Some(payload_digest) = payload_digest {
+        headers.insert(WarcHeader::PayloadDigest, payload_digest.into_bytes());
+    }
+}
+
+/// Entity body of an `application/http` block, after the blank line ending the headers
+fn http_payload(block: &[u8]) -> Option<&[u8]> {
+    block
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .map(|end| &block[end + 4..])
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use warc::RawRecordHeader;
+
+    fn record(record_type: &str, body: &[u8]) -> WetRecord {
+        WetRecord {
+            headers: RawRecordHeader {
+                version: "1.1".to_string(),
+                headers: [(WarcHeader::WarcType, record_type.as_bytes().to_vec())]
+                    .into_iter()
+                    .collect(),
+            },
+            body: body.to_vec(),
+        }
+    }
+
+    #[test]
+    fn known_vectors() {
+        assert_eq!(
+            DigestAlgorithm::Sha1.digest(b"abc"),
+            "sha1:VGMT4NSHA2AWVOR6EVYXQUGCNSONBWE5"
+        );
+        assert_eq!(
+            DigestAlgorithm::Sha256.digest(b"abc"),
+            "sha256:XJ4BNP4PAHH6UQKBIDPF3LRCEOYAGYNDSYLXVHFUCD7WD4QACWWQ===="
+        );
+        assert_eq!(
+            DigestAlgorithm::Sha1.digest(b""),
+            "sha1:3I42H3S6NNFQ2MSVX7XZKYAYSCX5QBYJ"
+        );
+    }
+
+    #[test]
+    fn payload_digest_covers_the_entity_body_only() {
+        let block = b"HTTP/1.1 200 OK\r\ncontent-type: text/plain\r\ncontent-length: 3\r\n\r\nabc";
+        let mut response = record("response", block);
+        add_digests(&mut response, DigestAlgorithm::Sha1);
+        assert_eq!(
+            response.header(WarcHeader::PayloadDigest).unwrap(),
+            "sha1:VGMT4NSHA2AWVOR6EVYXQUGCNSONBWE5"
+        );
+        assert_eq!(
+            response.header(WarcHeader::BlockDigest).unwrap(),
+            DigestAlgorithm::Sha1.digest(block)
+        );
+    }
+
+    #[test]
+    fn payload_digest_by_record_type() {
+        let mut conversion = record("conversion", b"abc");
+        add_digests(&mut conversion, DigestAlgorithm::Sha256);
+        assert_eq!(
+            conversion.header(WarcHeader::PayloadDigest),
+            conversion.header(WarcHeader::BlockDigest)
+        );
+        let mut request = record("request", b"GET / HTTP/1.1\r\nhost: example.com\r\n\r\n");
+        add_digests(&mut request, DigestAlgorithm::Sha256);
+        assert!(request.header(WarcHeader::BlockDigest).is_some());
+        assert_eq!(request.header(WarcHeader::PayloadDigest), None);
+    }
+}
@@ -0,0 +1,310 @@
+use std::io::{Cursor, Read};
+use std::panic;
+
+use itertools::Itertools;
+use lopdf::{Document, Object};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use thiserror::Error;
+use url::Url;
+use zip::ZipArchive;
+
+const DOCX_MIME: &str = "application/vnd.openxmlformats-officedocument.wordprocessingml.document";
+const ODT_MIME: &str = "application/vnd.oasis.opendocument.text";
+
+/// Format of a fetched payload, decides how its text is extracted
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DocumentKind {
+    Html,
+    Pdf,
+    Docx,
+    Odt,
+}
+
+impl DocumentKind {
+    /// Kind of a payload from its `Content-Type`, its first bytes and the extension of its URL are
+    /// used when the type is missing or generic. Anything unknown is treated as HTML
+    pub fn detect(content_type: &str, url: &str, body: &[u8]) -> Self {
+        match content_type {
+            "application/pdf" | "application/x-pdf" => return Self::Pdf,
+            DOCX_MIME => return Self::Docx,
+            ODT_MIME => return Self::Odt,
+            "text/html" | "application/xhtml+xml" => return Self::Html,
+            _ => {}
+        }
+        if body.starts_with(b"%PDF-") {
+            return Self::Pdf;
+        }
+        if body.starts_with(b"PK\x03\x04") {
+            // the first entry of an ODT file is an uncompressed `mimetype` file holding its type
+            if body.len() > 38 && body[38..].starts_with(ODT_MIME.as_bytes()) {
+                return Self::Odt;
+            }
+            let extension = Url::parse(url)
+                .ok()
+                .and_then(|url| {
+                    url.path_segments()
+                        .and_then(|mut segments| segments.next_back().map(str::to_lowercase))
+                })
+                .unwrap_or_default();
+            if extension.ends_with(".docx") {
+                return Self::Docx;
+            }
+            if extension.ends_with(".odt") {
+                return Self::Odt;
+            }
+        }
+        Self::Html
+    }
+
+    /// Value of the `WARC-Text-Extractor` header of documents that are not HTML
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Html => "html",
+            Self::Pdf => "pdf",
+            Self::Docx => "docx",
+            Self::Odt => "odt",
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum DocumentError {
+    #[error("unreadable PDF : {0}")]
+    Pdf(String),
+    #[error("unreadable archive : {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("unreadable XML : {0}")]
+    Xml(#[from] quick_xml::Error),
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("not a document")]
+    NotADocument,
+}
+
+/// Plain text of a PDF, DOCX or ODT payload, one line per paragraph
+pub fn document_text(kind: DocumentKind, body: &[u8]) -> Result<String, DocumentError> {
+    let text = match kind {
+        DocumentKind::Pdf => pdf_text(body)?,
+        DocumentKind::Docx => xml_text(&zip_entry(body, "word/document.xml")?, &DOCX_TAGS)?,
+        DocumentKind::Odt => xml_text(&zip_entry(body, "content.xml")?, &ODT_TAGS)?,
+        DocumentKind::Html => return Err(DocumentError::NotADocument),
+    };
+    Ok(text
+        .lines()
+        .map(|line| line.split_whitespace().join(" "))
+        .filter(|line| !line.is_empty())
+        .join("\n"))
+}
+
+/// Targets of the link annotations of a PDF, resolved against the `url` it was fetched from
+pub fn pdf_links(body: &[u8], url: &str) -> Vec<String> {
+    let (Ok(document), Ok(base)) = (Document::load_mem(body), Url::parse(url)) else {
+        return vec![];
+    };
+    let mut links = vec![];
+    for page in document.get_pages().into_values() {
+        let Ok(annotations) = document.get_page_annotations(page) else {
+            continue;
+        };
+        for annotation in annotations {
+            if !annotation
+                .get(b"Subtype")
+                .and_then(Object::as_name)
+                .is_ok_and(|subtype| subtype == b"Link")
+            {
+                continue;
+            }
+            let uri = annotation
+                .get(b"A")
+                .and_then(|action| document.dereference(action))
+                .and_then(|(_, action)| action.as_dict())
+                .and_then(|action| action.get(b"URI"))
+                .and_then(|uri| document.dereference(uri))
+                .and_then(|(_, uri)| uri.as_str());
+            if let Ok(uri) = uri
+                && let Ok(link) = base.join(String::from_utf8_lossy(uri).trim())
+            {
+                links.push(link.to_string());
+            }
+        }
+    }
+    links.into_iter().unique().collect()
+}
+
+fn pdf_text(body: &[u8]) -> Result<String, DocumentError> {
+    // the font and encoding parsers of pdf-extract panic on some malformed files
+    panic::catch_unwind(|| pdf_extract::extract_text_from_mem(body))
+        .map_err(|_| DocumentError::Pdf("extraction panicked".to_string()))?
+        .map_err(|e| DocumentError::Pdf(e.to_string()))
+}
+
+fn zip_entry(body: &[u8], name: &str) -> Result<Vec<u8>, DocumentError> {
+    let mut archive = ZipArchive::new(Cursor::new(body))?;
+    let mut entry = archive.by_name(name)?;
+    let mut content = Vec::new();
+    entry.read_to_end(&mut content)?;
+    Ok(content)
+}
+
+/// Local names of the XML elements that make up the text of a word processing format
+struct TextTags {
+    /// elements ending a line
+    paragraphs: &'static [&'static [u8]],
+    /// empty elements standing for a line break
+    breaks: &'static [&'static [u8]],
+    /// empty elements standing for a tab
+    tabs: &'static [&'static [u8]],
+    /// empty elements standing for a space
+    spaces: &'static [&'static [u8]],
+    /// elements whose text is not part of the document body
+    skipped: &'static [&'static [u8]],
+}
+
+const DOCX_TAGS: TextTags = TextTags {
+    paragraphs: &[b"p"],
+    breaks: &[b"br", b"cr"],
+    tabs: &[b"tab"],
+    spaces: &[],
+    skipped: &[b"instrText", b"delText"],
+};
+
+const ODT_TAGS: TextTags = TextTags {
+    paragraphs: &[b"p", b"h"],
+    breaks: &[b"line-break"],
+    tabs: &[b"tab"],
+    spaces: &[b"s"],
+    skipped: &[b"tracked-changes", b"note-citation"],
+};
+
+fn xml_text(xml: &[u8], tags: &TextTags) -> Result<String, DocumentError> {
+    let mut reader = Reader::from_reader(xml);
+    let mut buffer = Vec::new();
+    let mut text = String::new();
+    let mut skipped_depth = 0;
+    loop {
+        match reader.read_event_into(&mut buffer)? {
+            Event::Start(element)
+                if skipped_depth > 0 || tags.skipped.contains(&element.local_name().as_ref()) =>
+            {
+                skipped_depth += 1;
+            }
+            Event::End(element) => {
+                let name = element.local_name();
+                if skipped_depth > 0 {
+                    skipped_depth -= 1;
+                } else if tags.paragraphs.contains(&name.as_ref()) {
+                    text.push('\n');
+                }
+            }
+            Event::Empty(element) if skipped_depth == 0 => {
+                let name = element.local_name();
+                if tags.breaks.contains(&name.as_ref()) || tags.paragraphs.contains(&name.as_ref()) {
+                    text.push('\n');
+                } else if tags.tabs.contains(&name.as_ref()) || tags.spaces.contains(&name.as_ref()) {
+                    text.push(' ');
+                }
+            }
+            Event::Text(content) if skipped_depth == 0 => {
+                text.push_str(&content.unescape()?);
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buffer.clear();
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+    use zip::{CompressionMethod, ZipWriter};
+
+    /// Zip archive holding `entries` in order, stored like the `mimetype` entry of ODT files
+    fn archive(entries: &[(&str, &str)]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        for (name, content) in entries {
+            writer.start_file(*name, options).unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn docx() -> Vec<u8> {
+        archive(&[(
+            "word/document.xml",
+            r#"<w:document xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main"><w:body>
+                <w:p><w:r><w:t>First</w:t></w:r><w:r><w:tab/><w:t>paragraph &amp; more</w:t></w:r></w:p>
+                <w:p><w:r><w:t>Second</w:t><w:br/><w:t>line</w:t></w:r></w:p>
+                <w:p><w:r><w:instrText>HYPERLINK "x"</w:instrText></w:r><w:del><w:r><w:delText>gone</w:delText></w:r></w:del></w:p>
+            </w:body></w:document>"#,
+        )])
+    }
+
+    fn odt() -> Vec<u8> {
+        archive(&[
+            ("mimetype", ODT_MIME),
+            (
+                "content.xml",
+                r#"<office:document-content xmlns:office="urn:oasis:names:tc:opendocument:xmlns:office:1.0"
+                    xmlns:text="urn:oasis:names:tc:opendocument:xmlns:text:1.0"><office:body><office:text>
+                    <text:tracked-changes><text:p>removed</text:p></text:tracked-changes>
+                    <text:h>Title</text:h>
+                    <text:p>Words<text:s/>apart<text:line-break/>next line</text:p>
+                </office:text></office:body></office:document-content>"#,
+            ),
+        ])
+    }
+
+    #[test]
+    fn detect_without_content_type() {
+        let url = "http://example.com/files/download";
+        assert_eq!(DocumentKind::detect("", url, &odt()), DocumentKind::Odt);
+        assert_eq!(DocumentKind::detect("", url, &docx()), DocumentKind::Html);
+        assert_eq!(
+            DocumentKind::detect(
+                "application/octet-stream",
+                "http://example.com/report.DOCX",
+                &docx()
+            ),
+            DocumentKind::Docx
+        );
+        assert_eq!(
+            DocumentKind::detect("", url, b"%PDF-1.7"),
+            DocumentKind::Pdf
+        );
+        assert_eq!(
+            DocumentKind::detect(DOCX_MIME, url, b""),
+            DocumentKind::Docx
+        );
+        assert_eq!(DocumentKind::detect("", url, b"<html>"), DocumentKind::Html);
+    }
+
+    #[test]
+    fn docx_text() {
+        let text = document_text(DocumentKind::Docx, &docx()).unwrap();
+        assert_eq!(text, "First paragraph & more\nSecond\nline");
+    }
+
+    #[test]
+    fn odt_text() {
+        let text = document_text(DocumentKind::Odt, &odt()).unwrap();
+        assert_eq!(text, "Title\nWords apart\nnext line");
+    }
+
+    #[test]
+    fn broken_archives_are_errors() {
+        assert!(matches!(
+            document_text(DocumentKind::Docx, b"PK\x03\x04 truncated"),
+            Err(DocumentError::Zip(_))
+        ));
+        assert!(matches!(
+            document_text(DocumentKind::Odt, &docx()),
+            Err(DocumentError::Zip(_))
+        ));
+    }
+}
@@ -0,0 +1,419 @@
+use std::mem;
+
+use itertools::Itertools;
+use soup::{NodeExt, QueryBuilderExt, Soup};
+
+/// Elements whose contents are never rendered as text
+const NON_CONTENT: &[&str] = &[
+    "head", "script", "style", "noscript", "template", "svg", "math", "canvas", "iframe",
+    "object", "embed", "select", "datalist",
+];
+
+/// Elements that start a new block of text
+const BLOCKS: &[&str] = &[
+    "address", "article", "aside", "blockquote", "body", "caption", "dd", "details", "dialog",
+    "div", "dl", "dt", "fieldset", "figcaption", "figure", "footer", "form", "h1", "h2", "h3",
+    "h4", "h5", "h6", "header", "hgroup", "hr", "li", "main", "nav", "ol", "p", "pre", "section",
+    "summary", "table", "tbody", "td", "tfoot", "th", "thead", "tr", "ul",
+];
+
+/// Page regions that hold no main content whatever their text looks like
+const BOILERPLATE_REGIONS: &[&str] = &["nav", "footer", "aside", "menu"];
+
+/// Page regions that hold the main content
+const CONTENT_REGIONS: &[&str] = &["article", "main"];
+
+/// Words of `class` and `id` tokens marking boilerplate regions, also matched with a trailing `s`
+const BOILERPLATE_LABELS: &[&str] = &[
+    "nav", "navbar", "navigation", "menu", "footer", "sidebar", "cookie", "consent", "gdpr",
+    "banner", "breadcrumb", "share", "sharing", "social", "comment", "promo", "ad", "advert",
+    "advertisement", "related", "widget", "popup", "modal", "subscribe", "newsletter", "masthead",
+];
+
+/// Words of `class` and `id` tokens marking content regions, also matched with a trailing `s`
+const CONTENT_LABELS: &[&str] = &["article", "content", "main", "post", "entry", "story"];
+
+/// First words of tokens describing the page layout rather than the element, as `no-sidebar`
+/// or `has-comments`
+const MODIFIER_WORDS: &[&str] = &["no", "has", "with", "without"];
+
+/// Characters per line used to turn a block into wrapped lines when measuring its text density
+const WRAP_WIDTH: usize = 80;
+
+/// How the text of a page is extracted for its conversion record
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextExtractor {
+    /// all rendered text, see `visible_text`
+    Visible,
+    /// the article body only, see `main_content`
+    MainContent,
+}
+
+impl TextExtractor {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "visible" => Some(Self::Visible),
+            "main_content" => Some(Self::MainContent),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Visible => "visible",
+            Self::MainContent => "main_content",
+        }
+    }
+
+    pub fn extract(&self, soup: &Soup) -> String {
+        match self {
+            Self::Visible => visible_text(soup),
+            Self::MainContent => main_content(soup),
+        }
+    }
+}
+
+/// Rendered text of the page : scripts, styles, hidden elements and comments are skipped,
+/// every block element goes on its own line and whitespace runs are collapsed
+pub fn visible_text(soup: &Soup) -> String {
+    text_blocks(soup).iter().map(|block| &block.text).join("\n")
+}
+
+/// Text of the article body, dropping navigation, footers, cookie banners, sidebars and other
+/// boilerplate. Blocks are kept by their text density and link density and those of their
+/// neighbours, following the density rules of boilerpipe. The innermost region around a block
+/// decides otherwise : blocks inside boilerplate regions (`<nav>`, `class="sidebar"`, ...) are
+/// always dropped and those inside content regions (`<article>`, `id="content"`, ...) only need
+/// a low link density. `html`, `body` and elements holding most of the text of the page are
+/// never boilerplate regions, whatever their class
+pub fn main_content(soup: &Soup) -> String {
+    let blocks = text_blocks(soup);
+    let empty = TextBlock::default();
+    let mut content = Vec::new();
+    for (i, block) in blocks.iter().enumerate() {
+        let previous = if i == 0 { &empty } else { &blocks[i - 1] };
+        let next = blocks.get(i + 1).unwrap_or(&empty);
+        let is_content = if block.boilerplate {
+            false
+        } else if block.content_region {
+            block.link_density() <= 0.33
+        } else {
+            density_rules(previous, block, next)
+        };
+        if is_content {
+            content.push(block.text.as_str());
+        }
+    }
+    content.join("\n")
+}
+
+/// Decision tree of boilerpipe's `DensityRulesClassifier`
+fn density_rules(previous: &TextBlock, current: &TextBlock, next: &TextBlock) -> bool {
+    if current.link_density() > 0.333333 {
+        return false;
+    }
+    if previous.link_density() <= 0.555556 {
+        if current.text_density() <= 9.0 {
+            next.text_density() > 10.0 || previous.text_density() > 4.0
+        } else {
+            next.text_density() != 0.0
+        }
+    } else {
+        next.text_density() > 11.0
+    }
+}
+
+#[derive(Default)]
+struct TextBlock {
+    text: String,
+    words: usize,
+    link_words: usize,
+    boilerplate: bool,
+    content_region: bool,
+}
+
+impl TextBlock {
+    fn link_density(&self) -> f64 {
+        if self.words == 0 {
+            return 0.0;
+        }
+        self.link_words as f64 / self.words as f64
+    }
+
+    /// Words per wrapped line
+    fn text_density(&self) -> f64 {
+        let lines = self.text.chars().count().div_ceil(WRAP_WIDTH).max(1);
+        self.words as f64 / lines as f64
+    }
+}
+
+fn text_blocks(soup: &Soup) -> Vec<TextBlock> {
+    let mut walker = BlockWalker {
+        page_chars: rendered_chars(&soup.get_handle()),
+        ..BlockWalker::default()
+    };
+    walker.walk(&soup.get_handle());
+    walker.end_block();
+    walker.blocks
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Region {
+    Boilerplate,
+    Content,
+}
+
+#[derive(Default)]
+struct BlockWalker {
+    blocks: Vec<TextBlock>,
+    current: String,
+    current_link_words: usize,
+    link_depth: usize,
+    /// regions the walk is in, outermost first
+    regions: Vec<Region>,
+    /// non-whitespace characters of the rendered text of the page
+    page_chars: usize,
+}
+
+impl BlockWalker {
+    fn walk<N: NodeExt + QueryBuilderExt>(&mut self, node: &N) {
+        if node.is_text() {
+            let text = node.text();
+            if self.link_depth > 0 {
+                self.current_link_words += text.split_whitespace().count();
+            }
+            self.current.push_str(&text);
+            return;
+        }
+        if node.is_element() {
+            let name = node.name().to_lowercase();
+            if NON_CONTENT.contains(&name.as_str()) || node.get("hidden").is_some() {
+                return;
+            }
+            if name == "br" {
+                self.end_block();
+                return;
+            }
+            let region = self.region(node, &name);
+            let link = name == "a";
+            let block = BLOCKS.contains(&name.as_str());
+            if block {
+                self.end_block();
+            }
+            self.regions.extend(region);
+            self.link_depth += link as usize;
+            for child in node.children() {
+                self.walk(&child);
+            }
+            if block {
+                self.end_block();
+            }
+            if region.is_some() {
+                self.regions.pop();
+            }
+            self.link_depth -= link as usize;
+        } else if node.is_document() {
+            for child in node.children() {
+                self.walk(&child);
+            }
+        }
+    }
+
+    /// Region `node` starts, by its name or the words of its `class` and `id`
+    fn region<N: NodeExt + QueryBuilderExt>(&self, node: &N, name: &str) -> Option<Region> {
+        let tokens: Vec<String> = [node.get("class"), node.get("id")]
+            .into_iter()
+            .flatten()
+            .flat_map(|label| label.split_whitespace().map(str::to_string).collect_vec())
+            .collect();
+        let boilerplate = (BOILERPLATE_REGIONS.contains(&name)
+            || tokens
+                .iter()
+                .any(|token| label_matches(token, BOILERPLATE_LABELS)))
+            && !matches!(name, "html" | "body")
+            && rendered_chars(node) * 2 <= self.page_chars;
+        if boilerplate {
+            Some(Region::Boilerplate)
+        } else if CONTENT_REGIONS.contains(&name)
+            || tokens
+                .iter()
+                .any(|token| label_matches(token, CONTENT_LABELS))
+        {
+            Some(Region::Content)
+        } else {
+            None
+        }
+    }
+
+    fn end_block(&mut self) {
+        let text = self.current.split_whitespace().join(" ");
+        if !text.is_empty() {
+            let region = self.regions.last();
+            self.blocks.push(TextBlock {
+                words: text.split(' ').count(),
+                text,
+                link_words: self.current_link_words,
+                boilerplate: region == Some(&Region::Boilerplate),
+                content_region: region == Some(&Region::Content),
+            });
+        }
+        self.current.clear();
+        self.current_link_words = 0;
+    }
+}
+
+/// Non-whitespace characters of the text `node` renders
+fn rendered_chars<N: NodeExt + QueryBuilderExt>(node: &N) -> usize {
+    if node.is_text() {
+        return node.text().chars().filter(|c| !c.is_whitespace()).count();
+    }
+    if node.is_element()
+        && (NON_CONTENT.contains(&node.name().to_lowercase().as_str())
+            || node.get("hidden").is_some())
+    {
+        return 0;
+    }
+    node.children().map(|child| rendered_chars(&child)).sum()
+}
+
+/// Whether one of the words of a `class` or `id` token is among `labels`, so that `site-nav`
+/// and `mainNav` match `nav` but `unavailable-note` does not
+fn label_matches(token: &str, labels: &[&str]) -> bool {
+    let words = label_words(token);
+    if words
+        .first()
+        .is_some_and(|word| MODIFIER_WORDS.contains(&word.as_str()))
+    {
+        return false;
+    }
+    words.iter().any(|word| {
+        labels.contains(&word.as_str())
+            || word
+                .strip_suffix('s')
+                .is_some_and(|singular| labels.contains(&singular))
+    })
+}
+
+/// Lowercase words of a token split on `-`, `_` and camel case humps
+fn label_words(token: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut after_lowercase = false;
+    for c in token.chars() {
+        let separator = c == '-' || c == '_';
+        if (separator || (c.is_uppercase() && after_lowercase)) && !word.is_empty() {
+            words.push(mem::take(&mut word));
+        }
+        if !separator {
+            word.extend(c.to_lowercase());
+        }
+        after_lowercase = c.is_lowercase() || c.is_ascii_digit();
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIRST: &str = "The river rose through the night and by morning the lower town was under water, \
+                         with boats carrying families from the roofs of their houses to the hills.";
+    const SECOND: &str = "Engineers said the old levee had held for fifty years but the rain of the last \
+                          week was heavier than anything recorded since the station opened in the valley.";
+
+    fn page(body: &str) -> Soup {
+        Soup::new(&format!(
+            "<html><head><title>Flood</title><script>var x = 1;</script></head>{body}</html>"
+        ))
+    }
+
+    fn article() -> String {
+        format!(
+            "<nav><a href='/'>Home</a> <a href='/news'>News</a> <a href='/sport'>Sport</a></nav>\
+             <div><h1>Flood in the valley</h1><p>{FIRST}</p><p>{SECOND}</p></div>\
+             <footer><a href='/about'>About</a> <a href='/contact'>Contact</a></footer>"
+        )
+    }
+
+    #[test]
+    fn keeps_the_article_and_drops_navigation() {
+        let text = main_content(&page(&format!("<body>{}</body>", article())));
+        assert!(text.contains(FIRST));
+        assert!(text.contains(SECOND));
+        assert!(!text.contains("Home"));
+        assert!(!text.contains("Contact"));
+    }
+
+    #[test]
+    fn body_classes_are_not_boilerplate() {
+        let soup = page(&format!(
+            "<body class=\"page no-sidebar\">{}</body>",
+            article()
+        ));
+        let text = main_content(&soup);
+        assert!(text.contains(FIRST));
+        assert!(text.contains(SECOND));
+    }
+
+    #[test]
+    fn form_wrapping_the_page_is_not_boilerplate() {
+        let soup = page(&format!(
+            "<body><form id=\"form1\" method=\"post\">{}</form></body>",
+            article()
+        ));
+        let text = main_content(&soup);
+        assert!(text.contains(FIRST));
+        assert!(text.contains(SECOND));
+        assert!(!text.contains("Home"));
+    }
+
+    #[test]
+    fn wrapper_holding_most_of_the_text_is_not_boilerplate() {
+        let soup = page(&format!(
+            "<body><div id=\"menu-wrapper\">{}</div></body>",
+            article()
+        ));
+        assert!(main_content(&soup).contains(FIRST));
+    }
+
+    #[test]
+    fn labels_match_whole_words() {
+        let soup = page(&format!(
+            "<body><div><p>{FIRST}</p><div class=\"unavailable-note\"><p>{SECOND}</p></div>\
+             <p>{FIRST}</p></div><footer><a href='/about'>About</a></footer></body>"
+        ));
+        assert!(main_content(&soup).contains(SECOND));
+    }
+
+    #[test]
+    fn content_region_inside_boilerplate_is_kept() {
+        let soup = page(&format!(
+            "<body>{}<div class=\"related-stories\"><p>More</p>\
+             <article><p>Inside the related box.</p></article></div></body>",
+            article()
+        ));
+        let text = main_content(&soup);
+        assert!(text.contains("Inside the related box."));
+        assert!(!text.contains("More"));
+    }
+
+    #[test]
+    fn label_words_split_tokens() {
+        assert_eq!(label_words("site-nav"), ["site", "nav"]);
+        assert_eq!(label_words("mainNav_bar"), ["main", "nav", "bar"]);
+        assert!(label_matches("site-nav", BOILERPLATE_LABELS));
+        assert!(label_matches("comments", BOILERPLATE_LABELS));
+        assert!(!label_matches("unavailable-note", BOILERPLATE_LABELS));
+        assert!(!label_matches("no-sidebar", BOILERPLATE_LABELS));
+        assert!(label_matches("entry-content", CONTENT_LABELS));
+    }
+
+    #[test]
+    fn visible_text_skips_scripts_and_hidden_elements() {
+        let soup = page("<body><p>Shown <span hidden>secret</span>text</p><p>Next</p></body>");
+        assert_eq!(visible_text(&soup), "Shown text\nNext");
+    }
+}
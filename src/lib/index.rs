@@ -0,0 +1,319 @@
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+
+use serde_json::json;
+use url::{Host, Url};
+use warc::{RecordType, WarcHeader};
+
+use crate::canonical::Canonicalizer;
+use crate::response::WetRecord;
+
+const CDX_HEADER: &str = " CDX N b a m s k r M S V g";
+
+/// Index files written next to every output file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndexFormat {
+    /// `<file>.cdxj` : SURT, timestamp and a JSON block per line, as read by pywb
+    Cdxj,
+    /// `<file>.cdx` : classic space separated `CDX N b a m s k r M S V g` lines
+    Cdx,
+}
+
+impl IndexFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "cdxj" => Some(Self::Cdxj),
+            "cdx" => Some(Self::Cdx),
+            _ => None,
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            Self::Cdxj => "cdxj",
+            Self::Cdx => "cdx",
+        }
+    }
+}
+
+/// Location and description of one record of an output file
+pub struct IndexEntry {
+    surt: String,
+    timestamp: String,
+    url: String,
+    mime: String,
+    status: String,
+    digest: String,
+    length: u64,
+    offset: u64,
+    filename: String,
+}
+
+impl IndexEntry {
+    /// Describes `record`, `warcinfo` and `request` records are not indexed. Its SURT is built from
+    /// the key `canonicalizer` gives its URL
+    pub fn from_record(record: &WetRecord, canonicalizer: &Canonicalizer) -> Option<Self> {
+        let record_type = RecordType::from(record.header(WarcHeader::WarcType)?);
+        if matches!(record_type, RecordType::WarcInfo | RecordType::Request) {
+            return None;
+        }
+        let url = record.header(WarcHeader::TargetURI)?;
+        let timestamp = record
+            .header(WarcHeader::Date)
+            .map(|date| date.chars().filter(char::is_ascii_digit).take(14).collect())
+            .unwrap_or_default();
+        let (mime, status) = match record_type {
+            RecordType::Response => (
+                record.header(WarcHeader::IdentifiedPayloadType),
+                http_status(&record.body),
+            ),
+            _ => (record.header(WarcHeader::ContentType), None),
+        };
+        let digest = record
+            .header(WarcHeader::PayloadDigest)
+            .or_else(|| record.header(WarcHeader::BlockDigest))
+            .map(|digest| match digest.strip_prefix("sha1:") {
+                Some(sha1) => sha1.to_string(),
+                None => digest,
+            })
+            .unwrap_or_else(|| "-".to_string());
+        Some(Self {
+            surt: surt(&canonicalizer.canonicalize(&url)),
+            timestamp,
+            url,
+            mime: mime.unwrap_or_else(|| "-".to_string()),
+            status: status.unwrap_or_else(|| "-".to_string()),
+            digest,
+            length: 0,
+            offset: 0,
+            filename: String::new(),
+        })
+    }
+
+    pub fn locate(&mut self, filename: &str, offset: u64, length: u64) {
+        self.filename = filename.to_string();
+        self.offset = offset;
+        self.length = length;
+    }
+
+    fn to_cdxj(&self) -> String {
+        let block = json!({
+            "url": self.url,
+            "mime": self.mime,
+            "status": self.status,
+            "digest": self.digest,
+            "length": self.length.to_string(),
+            "offset": self.offset.to_string(),
+            "filename": self.filename,
+        });
+        format!("{} {} {block}", self.surt, self.timestamp)
+    }
+
+    fn to_cdx(&self) -> String {
+        format!(
+            "{} {} {} {} {} {} - - {} {} {}",
+            self.surt,
+            self.timestamp,
+            self.url,
+            self.mime,
+            self.status,
+            self.digest,
+            self.length,
+            self.offset,
+            self.filename
+        )
+    }
+}
+
+/// Writes the sorted `entries` of `warc_path` to `<warc_path>.<format>` for every format
+pub fn write_indexes(
+    warc_path: &str,
+    entries: &[IndexEntry],
+    formats: &[IndexFormat],
+) -> io::Result<()> {
+    for format in formats {
+        let mut lines: Vec<String> = entries
+            .iter()
+            .map(|entry| match format {
+                IndexFormat::Cdxj => entry.to_cdxj(),
+                IndexFormat::Cdx => entry.to_cdx(),
+            })
+            .collect();
+        lines.sort();
+        let mut index = BufWriter::new(File::create(format!(
+            "{warc_path}.{}",
+            format.extension()
+        ))?);
+        if *format == IndexFormat::Cdx {
+            writeln!(index, "{CDX_HEADER}")?;
+        }
+        for line in lines {
+            writeln!(index, "{line}")?;
+        }
+        index.flush()?;
+    }
+    Ok(())
+}
+
+/// Sort-friendly URL form used as the index key : `http://www.Example.com:80/a?b` becomes
+/// `com,example)/a?b`
+pub fn surt(url: &str) -> String {
+    let url = match Url::parse(url) {
+        Ok(url) => url,
+        Err(_) => return url.to_lowercase(),
+    };
+    let mut surt = match url.host() {
+        Some(Host::Domain(domain)) => {
+            let domain = domain.to_lowercase();
+            let domain = domain.strip_prefix("www.").unwrap_or(&domain);
+            domain.split('.').rev().collect::<Vec<&str>>().join(",")
+        }
+        Some(ip) => ip.to_string(),
+        None => String::new(),
+    };
+    if let Some(port) = url.port() {
+        surt.push(':');
+        surt.push_str(&port.to_string());
+    }
+    surt.push(')');
+    surt.push_str(&url.path().to_lowercase());
+    if let Some(query) = url.query() {
+        surt.push('?');
+        surt.push_str(&query.to_lowercase());
+    }
+    surt
+}
+
+/// Status code from the status line of an `application/http` response block
+fn http_status(block: &[u8]) -> Option<String> {
+    let line_end = block.iter().position(|&byte| byte == b'\r')?;
+    let status_line = String::from_utf8_lossy(&block[..line_end]);
+    status_line.split(' ').nth(1).map(|code| code.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use warc::RawRecordHeader;
+
+    use super::*;
+
+    fn record(headers: Vec<(WarcHeader, &str)>, body: &[u8]) -> WetRecord {
+        WetRecord {
+            headers: RawRecordHeader {
+                version: "1.1".to_string(),
+                headers: headers
+                    .into_iter()
+                    .map(|(name, value)| (name, value.as_bytes().to_vec()))
+                    .collect(),
+            },
+            body: body.to_vec(),
+        }
+    }
+
+    fn conversion() -> WetRecord {
+        record(
+            vec![
+                (WarcHeader::WarcType, "conversion"),
+                (WarcHeader::TargetURI, "http://www.Example.com/A?b=1"),
+                (WarcHeader::Date, "2026-10-18T07:15:40Z"),
+                (WarcHeader::ContentType, "text/plain"),
+                (WarcHeader::BlockDigest, "sha1:ABCDEF"),
+            ],
+            b"text",
+        )
+    }
+
+    #[test]
+    fn surt_reverses_domains_and_lowercases() {
+        assert_eq!(
+            surt("http://www.Example.com/A/b?X=1"),
+            "com,example)/a/b?x=1"
+        );
+        assert_eq!(surt("https://sub.example.co.uk/"), "uk,co,example,sub)/");
+    }
+
+    #[test]
+    fn surt_keeps_explicit_ports_only() {
+        assert_eq!(surt("http://example.com:8080/a"), "com,example:8080)/a");
+        assert_eq!(surt("http://example.com:80/a"), "com,example)/a");
+        assert_eq!(surt("https://example.com:443/a"), "com,example)/a");
+    }
+
+    #[test]
+    fn surt_of_ip_hosts() {
+        assert_eq!(surt("http://127.0.0.1:8765/a"), "127.0.0.1:8765)/a");
+        assert_eq!(surt("http://[2001:DB8::1]/a"), "[2001:db8::1])/a");
+        assert_eq!(surt("http://[2001:db8::1]:8080/"), "[2001:db8::1]:8080)/");
+    }
+
+    #[test]
+    fn cdx_line() {
+        let mut entry = IndexEntry::from_record(&conversion(), &Canonicalizer::default()).unwrap();
+        entry.locate("crawl-00000.warc.gz", 1234, 567);
+        assert_eq!(
+            entry.to_cdx(),
+            "com,example)/a?b=1 20261018071540 http://www.Example.com/A?b=1 text/plain - ABCDEF - - \
+             567 1234 crawl-00000.warc.gz"
+        );
+    }
+
+    #[test]
+    fn cdxj_line() {
+        let mut entry = IndexEntry::from_record(&conversion(), &Canonicalizer::default()).unwrap();
+        entry.locate("crawl-00000.warc.gz", 1234, 567);
+        let line = entry.to_cdxj();
+        let mut fields = line.splitn(3, ' ');
+        assert_eq!(fields.next(), Some("com,example)/a?b=1"));
+        assert_eq!(fields.next(), Some("20261018071540"));
+        let block: serde_json::Value = serde_json::from_str(fields.next().unwrap()).unwrap();
+        assert_eq!(
+            block,
+            json!({
+                "url": "http://www.Example.com/A?b=1",
+                "mime": "text/plain",
+                "status": "-",
+                "digest": "ABCDEF",
+                "length": "567",
+                "offset": "1234",
+                "filename": "crawl-00000.warc.gz",
+            })
+        );
+    }
+
+    #[test]
+    fn response_entry_reads_status_and_payload() {
+        let response = record(
+            vec![
+                (WarcHeader::WarcType, "response"),
+                (WarcHeader::TargetURI, "http://example.com/"),
+                (WarcHeader::Date, "2026-10-18T07:15:40Z"),
+                (
+                    WarcHeader::ContentType,
+                    "application/http; msgtype=response",
+                ),
+                (WarcHeader::IdentifiedPayloadType, "text/html"),
+                (WarcHeader::BlockDigest, "sha1:BLOCK"),
+                (WarcHeader::PayloadDigest, "sha1:PAYLOAD"),
+            ],
+            b"HTTP/1.1 404 Not Found\r\n\r\n",
+        );
+        let entry = IndexEntry::from_record(&response, &Canonicalizer::default()).unwrap();
+        assert_eq!(
+            entry.to_cdx(),
+            "com,example)/ 20261018071540 http://example.com/ text/html 404 PAYLOAD - - 0 0 "
+        );
+    }
+
+    #[test]
+    fn requests_are_not_indexed() {
+        let request = record(
+            vec![
+                (WarcHeader::WarcType, "request"),
+                (WarcHeader::TargetURI, "http://example.com/"),
+            ],
+            b"GET / HTTP/1.1\r\n\r\n",
+        );
+        assert!(IndexEntry::from_record(&request, &Canonicalizer::default()).is_none());
+    }
+}
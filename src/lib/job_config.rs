@@ -1,8 +1,22 @@
-use config::{Config, ConfigBuilder, ConfigError, Source};
+use config::{Config, ConfigBuilder, ConfigError, Map, Source, Value};
 use config::builder::DefaultState;
 use phf::phf_map;
 use thiserror::Error;
 
+use crate::canonical;
+use crate::crawl::{ArchiveMode, LanguageArchiving};
+use crate::crawl_utils::array_stringify;
+use crate::digest::DigestAlgorithm;
+use crate::extract::TextExtractor;
+use crate::index::IndexFormat;
+use crate::lang;
+use crate::lang::DetectorBackend;
+use crate::links::{self, LinkSource};
+use crate::mime::{self, valid_pattern};
+use crate::quality::valid_filters;
+use crate::scope::{self, ScopeMode};
+use crate::sink::valid_sink;
+use crate::writer::DEFAULT_NAME_TEMPLATE;
 use crate::job_config::CrawlerConfigError::{MandatoryFieldMissing, WrongFieldType};
 
 static TYPE_CHECKS: phf::Map<&'static str, &'static str> = phf_map! {
@@ -11,10 +25,49 @@ static TYPE_CHECKS: phf::Map<&'static str, &'static str> = phf_map! {
             "link_timeout"=>"uint",
             "crawl_tasks"=>"uint",
             "crawl_recursion"=>"uint",
-            "accept_languages"=>"vec<string>",
-            "respect_robots" => "bool"
+            "accept_languages"=>"languages",
+            "detect_languages"=>"languages",
+            "language_min_share" => "ratio",
+            "language_min_confidence" => "ratio",
+            "language_detector" => "detector",
+            "archive_languages" => "archive_languages",
+            "respect_robots" => "bool",
+            "respect_noindex" => "bool",
+            "respect_noarchive" => "bool",
+            "respect_nofollow" => "bool",
+            "respect_link_nofollow" => "bool",
+            "archive_mode" => "archive_mode",
+            "operator" => "string",
+            "warc_digest" => "digest",
+            "warc_max_size" => "uint",
+            "warc_max_records" => "uint",
+            "warc_max_age" => "uint",
+            "warc_name_template" => "name_template",
+            "warc_index" => "index",
+            "sinks" => "sinks",
+            "text_extractor" => "extractor",
+            "mime_allow" => "mime",
+            "mime_deny" => "mime",
+            "quality_filters" => "quality",
+            "link_sources" => "link_sources",
+            "canonical_strip_params" => "vec<string>",
+            "canonical_merge_schemes" => "bool",
+            "canonical_merge_www" => "bool",
+            "scope_mode" => "scope_mode",
+            "scope_subdomains" => "bool",
+            "scope_allow_domains" => "vec<string>",
+            "scope_deny_domains" => "vec<string>",
+            "scope_include" => "regexes",
+            "scope_exclude" => "regexes",
+            "scope_protocols" => "protocols"
 };
 
+/// Values of `language_detector` in this build
+#[cfg(feature = "lingua")]
+const DETECTORS: &str = "\"whatlang\" or \"lingua\"";
+#[cfg(not(feature = "lingua"))]
+const DETECTORS: &str = "\"whatlang\" (\"lingua\" needs the lingua feature)";
+
 // static CONFIG_DEFAULTS: phf::Map<&'static str, ValueKind> = phf_map! {
 //             "crawl_tasks" => ValueKind::U64(50),
 //             "link_timeout" => ValueKind::U64(5000),
@@ -74,6 +127,133 @@ pub fn check_config(config: &Config) -> Option<Vec<CrawlerConfigError>> {
                     errors.push(WrongFieldType(k.to_string(), r#type.to_string()))
                 }
             }
+            "name_template"
+                if v
+                    .clone()
+                    .into_string()
+                    .map_or(true, |template| !template.contains("{serial}")) =>
+            {
+                errors.push(WrongFieldType(k.to_string(), "string containing \"{serial}\"".to_string()))
+            }
+            "archive_mode"
+                if v
+                    .clone()
+                    .into_string()
+                    .map_or(true, |mode| ArchiveMode::from_name(&mode).is_none()) =>
+            {
+                errors.push(WrongFieldType(k.to_string(), "\"text\" or \"full\"".to_string()))
+            }
+            "archive_languages"
+                if v
+                    .clone()
+                    .into_string()
+                    .map_or(true, |name| LanguageArchiving::from_name(&name).is_none()) =>
+            {
+                errors.push(WrongFieldType(k.to_string(), "\"all\", \"accepted\" or \"separate\"".to_string()))
+            }
+            "digest"
+                if v
+                    .clone()
+                    .into_string()
+                    .map_or(true, |name| DigestAlgorithm::from_name(&name).is_none()) =>
+            {
+                errors.push(WrongFieldType(k.to_string(), "\"sha1\" or \"sha256\"".to_string()))
+            }
+            "index"
+                if v.clone().into_array().map_or(true, |array| {
+                    array.into_iter().any(|format| {
+                        format
+                            .into_string()
+                            .map_or(true, |name| IndexFormat::from_name(&name).is_none())
+                    })
+                }) =>
+            {
+                errors.push(WrongFieldType(k.to_string(), "list of \"cdxj\" or \"cdx\"".to_string()))
+            }
+            "sinks"
+                if v.clone().into_array().map_or(true, |array| {
+                    array
+                        .into_iter()
+                        .any(|sink| sink.into_string().map_or(true, |name| !valid_sink(&name)))
+                }) =>
+            {
+                errors.push(WrongFieldType(k.to_string(), "list of \"warc\", \"jsonl\" or \"text\"".to_string()))
+            }
+            "extractor"
+                if v
+                    .clone()
+                    .into_string()
+                    .map_or(true, |name| TextExtractor::from_name(&name).is_none()) =>
+            {
+                errors.push(WrongFieldType(k.to_string(), "\"visible\" or \"main_content\"".to_string()))
+            }
+            "mime"
+                if v.clone().into_array().map_or(true, |array| {
+                    array.into_iter().any(|pattern| {
+                        pattern.into_string().map_or(true, |pattern| !valid_pattern(&pattern))
+                    })
+                }) =>
+            {
+                errors.push(WrongFieldType(k.to_string(), "list of \"type/subtype\" patterns, `*` matching any".to_string()))
+            }
+            "quality" if !valid_filters(v) => {
+                errors.push(WrongFieldType(k.to_string(), "table of checks, each a { value = number, action = \"drop\", \"flag\" or \"nofollow\" } table".to_string()))
+            }
+            "link_sources"
+                if v.clone().into_array().map_or(true, |array| {
+                    array.into_iter().any(|source| {
+                        source.into_string().map_or(true, |name| LinkSource::from_name(&name).is_none())
+                    })
+                }) =>
+            {
+                errors.push(WrongFieldType(k.to_string(), format!("list of {}", LinkSource::ALL.iter().map(|source| format!("\"{}\"", source.name())).collect::<Vec<String>>().join(", "))))
+            }
+            "scope_mode"
+                if v
+                    .clone()
+                    .into_string()
+                    .map_or(true, |mode| ScopeMode::from_name(&mode).is_none()) =>
+            {
+                errors.push(WrongFieldType(k.to_string(), "\"any\", \"host\" or \"domain\"".to_string()))
+            }
+            "protocols"
+                if v.clone().into_array().map_or(true, |array| {
+                    array.into_iter().any(|protocol| {
+                        protocol.into_string().map_or(true, |protocol| !scope::valid_protocol(&protocol))
+                    })
+                }) =>
+            {
+                errors.push(WrongFieldType(k.to_string(), format!("list of {}", scope::PROTOCOLS.iter().map(|protocol| format!("\"{protocol}\"")).collect::<Vec<String>>().join(", "))))
+            }
+            "regexes"
+                if v.clone().into_array().map_or(true, |array| {
+                    array.into_iter().any(|pattern| {
+                        pattern.into_string().map_or(true, |pattern| !scope::valid_pattern(&pattern))
+                    })
+                }) =>
+            {
+                errors.push(WrongFieldType(k.to_string(), "list of regular expressions".to_string()))
+            }
+            "languages"
+                if v.clone().into_array().map_or(true, |array| {
+                    array.into_iter().any(|lang| {
+                        lang.into_string().map_or(true, |lang| lang::str_to_lang(&lang).is_none())
+                    })
+                }) =>
+            {
+                errors.push(WrongFieldType(k.to_string(), "list of ISO 639-1 or 639-3 codes or English names of languages supported by whatlang".to_string()))
+            }
+            "ratio" if v.clone().into_float().map_or(true, |ratio| !(0.0..=1.0).contains(&ratio)) => {
+                errors.push(WrongFieldType(k.to_string(), "number between 0 and 1".to_string()))
+            }
+            "detector"
+                if v
+                    .clone()
+                    .into_string()
+                    .map_or(true, |name| DetectorBackend::from_name(&name).is_none()) =>
+            {
+                errors.push(WrongFieldType(k.to_string(), format!("one of {}", DETECTORS)))
+            }
             _ => continue,
         }
     }
@@ -84,6 +264,31 @@ pub fn check_config(config: &Config) -> Option<Vec<CrawlerConfigError>> {
     }
 }
 
+/// The job as `name: value` pairs, recorded in the `warcinfo` record of every output file.
+/// Tables such as `quality_filters` are flattened to dotted names
+pub fn job_fields(config: &Config) -> Vec<(String, String)> {
+    let mut fields = Vec::new();
+    for (k, v) in config.collect().unwrap() {
+        push_field(&mut fields, k, v);
+    }
+    fields.sort();
+    fields
+}
+
+fn push_field(fields: &mut Vec<(String, String)>, name: String, value: Value) {
+    if let Ok(table) = value.clone().into_table() {
+        for (k, v) in table {
+            push_field(fields, format!("{name}.{k}"), v);
+        }
+        return;
+    }
+    let value = match value.clone().into_array() {
+        Ok(array) => array_stringify(&array, ','),
+        Err(_) => value.to_string(),
+    };
+    fields.push((name, value));
+}
+
 pub fn default_config() -> ConfigBuilder<DefaultState> {
     let accept_languages: Vec<String> = Vec::new();
     Config::builder()
@@ -98,10 +303,74 @@ pub fn default_config() -> ConfigBuilder<DefaultState> {
             get_time_string(),
         )
         .unwrap()
-        .set_default("accept_languages", accept_languages)
+        .set_default("accept_languages", accept_languages.clone())
+        .unwrap()
+        .set_default("detect_languages", accept_languages)
+        .unwrap()
+        .set_default("language_min_share", 0.5)
+        .unwrap()
+        .set_default("language_min_confidence", 0.5)
+        .unwrap()
+        .set_default("language_detector", "whatlang")
+        .unwrap()
+        .set_default("archive_languages", "all")
         .unwrap()
         .set_default("respect_robots", true)
         .unwrap()
+        .set_default("respect_noindex", true)
+        .unwrap()
+        .set_default("respect_noarchive", true)
+        .unwrap()
+        .set_default("respect_nofollow", true)
+        .unwrap()
+        .set_default("respect_link_nofollow", true)
+        .unwrap()
+        .set_default("archive_mode", "text")
+        .unwrap()
+        .set_default("warc_digest", "sha1")
+        .unwrap()
+        .set_default("warc_max_size", 0)
+        .unwrap()
+        .set_default("warc_max_records", 0)
+        .unwrap()
+        .set_default("warc_max_age", 0)
+        .unwrap()
+        .set_default("warc_name_template", DEFAULT_NAME_TEMPLATE)
+        .unwrap()
+        .set_default("warc_index", vec!["cdxj"])
+        .unwrap()
+        .set_default("sinks", vec!["warc"])
+        .unwrap()
+        .set_default("text_extractor", "visible")
+        .unwrap()
+        .set_default("mime_allow", mime::DEFAULT_ALLOW.to_vec())
+        .unwrap()
+        .set_default("mime_deny", Vec::<String>::new())
+        .unwrap()
+        .set_default("quality_filters", Map::<String, Value>::new())
+        .unwrap()
+        .set_default("link_sources", links::DEFAULT_SOURCES.to_vec())
+        .unwrap()
+        .set_default("canonical_strip_params", canonical::DEFAULT_STRIP_PARAMS.to_vec())
+        .unwrap()
+        .set_default("canonical_merge_schemes", false)
+        .unwrap()
+        .set_default("canonical_merge_www", false)
+        .unwrap()
+        .set_default("scope_mode", "any")
+        .unwrap()
+        .set_default("scope_subdomains", false)
+        .unwrap()
+        .set_default("scope_allow_domains", Vec::<String>::new())
+        .unwrap()
+        .set_default("scope_deny_domains", Vec::<String>::new())
+        .unwrap()
+        .set_default("scope_include", Vec::<String>::new())
+        .unwrap()
+        .set_default("scope_exclude", Vec::<String>::new())
+        .unwrap()
+        .set_default("scope_protocols", vec!["http", "https"])
+        .unwrap()
 }
 
 #[cfg(not(target_os = "linux"))]
@@ -1,45 +1,347 @@
+use phf::phf_map;
 use whatlang::{Detector, Lang};
 
-#[inline(always)]
-fn str_to_lang(lang: &str) -> Option<Lang> {
-    let lang = lang.to_lowercase();
-    match lang.as_str() {
-        "arabic" | "ar" => Some(Lang::Ara),
-        "english" | "en" => Some(Lang::Eng),
-        "french" | "fr" => Some(Lang::Fra),
-        _ => None,
-    }
+/// ISO 639-1 codes of the languages whatlang detects
+static ISO_639_1: phf::Map<&'static str, Lang> = phf_map! {
+    "af" => Lang::Afr, "ak" => Lang::Aka, "am" => Lang::Amh, "ar" => Lang::Ara, "az" => Lang::Aze,
+    "be" => Lang::Bel, "bg" => Lang::Bul, "bn" => Lang::Ben, "ca" => Lang::Cat, "cs" => Lang::Ces,
+    "da" => Lang::Dan, "de" => Lang::Deu, "el" => Lang::Ell, "en" => Lang::Eng, "eo" => Lang::Epo,
+    "es" => Lang::Spa, "et" => Lang::Est, "fa" => Lang::Pes, "fi" => Lang::Fin, "fr" => Lang::Fra,
+    "gu" => Lang::Guj, "he" => Lang::Heb, "hi" => Lang::Hin, "hr" => Lang::Hrv, "hu" => Lang::Hun,
+    "hy" => Lang::Hye, "id" => Lang::Ind, "it" => Lang::Ita, "ja" => Lang::Jpn, "jv" => Lang::Jav,
+    "ka" => Lang::Kat, "km" => Lang::Khm, "kn" => Lang::Kan, "ko" => Lang::Kor, "la" => Lang::Lat,
+    "lt" => Lang::Lit, "lv" => Lang::Lav, "mk" => Lang::Mkd, "ml" => Lang::Mal, "mr" => Lang::Mar,
+    "my" => Lang::Mya, "nb" => Lang::Nob, "ne" => Lang::Nep, "nl" => Lang::Nld, "no" => Lang::Nob,
+    "or" => Lang::Ori, "pa" => Lang::Pan, "pl" => Lang::Pol, "pt" => Lang::Por, "ro" => Lang::Ron,
+    "ru" => Lang::Rus, "si" => Lang::Sin, "sk" => Lang::Slk, "sl" => Lang::Slv, "sn" => Lang::Sna,
+    "sr" => Lang::Srp, "sv" => Lang::Swe, "ta" => Lang::Tam, "te" => Lang::Tel, "th" => Lang::Tha,
+    "tk" => Lang::Tuk, "tl" => Lang::Tgl, "tr" => Lang::Tur, "uk" => Lang::Ukr, "ur" => Lang::Urd,
+    "uz" => Lang::Uzb, "vi" => Lang::Vie, "yi" => Lang::Yid, "zh" => Lang::Cmn, "zu" => Lang::Zul,
+};
+
+/// Common codes and names of whatlang languages that differ from `Lang::code` and `Lang::eng_name`
+static ALIASES: phf::Map<&'static str, Lang> = phf_map! {
+    "zho" => Lang::Cmn, "chinese" => Lang::Cmn,
+    "fas" => Lang::Pes, "farsi" => Lang::Pes,
+    "nor" => Lang::Nob, "norwegian" => Lang::Nob,
+    "fil" => Lang::Tgl, "filipino" => Lang::Tgl,
+    "slovenian" => Lang::Slv,
+    "sinhala" => Lang::Sin,
+    "odia" => Lang::Ori,
+};
+
+/// Language named by an ISO 639-1 or ISO 639-3 code or an English name, `None` when whatlang
+/// cannot detect it
+pub fn str_to_lang(lang: &str) -> Option<Lang> {
+    let lang = lang.trim().to_lowercase();
+    ISO_639_1
+        .get(lang.as_str())
+        .or_else(|| ALIASES.get(lang.as_str()))
+        .copied()
+        .or_else(|| Lang::from_code(lang.as_str()))
+        .or_else(|| {
+            Lang::all()
+                .iter()
+                .find(|language| language.eng_name().eq_ignore_ascii_case(&lang))
+                .copied()
+        })
 }
 
 pub fn lang_builder(langs: Vec<&str>) -> Vec<Lang> {
     let mut detect_langs = Vec::new();
     for lang in langs {
-        if let Some(language) = str_to_lang(lang) {
+        if let Some(language) = str_to_lang(lang) && !detect_langs.contains(&language) {
             detect_langs.push(language)
         }
     }
     detect_langs
 }
 
-pub fn build_langdetector(langs: Vec<&str>) -> Detector {
+/// Identifies the language of a piece of text, implemented by every detection backend
+pub trait LanguageDetector: Send + Sync {
+    /// Language of `text` and the confidence of the detection, between 0 and 1
+    fn detect(&self, text: &str) -> Option<(Lang, f64)>;
+}
+
+impl LanguageDetector for Detector {
+    fn detect(&self, text: &str) -> Option<(Lang, f64)> {
+        Detector::detect(self, text).map(|info| (info.lang(), info.confidence()))
+    }
+}
+
+/// n-gram models of lingua, slower and heavier than whatlang but more accurate on short texts
+/// and closely related languages. The models are compiled into the binary so it runs offline.
+/// Only the languages both libraries know can be detected
+#[cfg(feature = "lingua")]
+pub struct LinguaDetector {
+    detector: lingua::LanguageDetector,
+}
+
+#[cfg(feature = "lingua")]
+impl LinguaDetector {
+    /// Detector choosing between `langs`, or between every language it shares with whatlang when
+    /// it knows none of them
+    pub fn new(langs: &[Lang]) -> Self {
+        let shared: Vec<(lingua::Language, Lang)> = lingua::Language::all()
+            .into_iter()
+            .filter_map(|language| {
+                str_to_lang(&language.iso_code_639_3().to_string()).map(|lang| (language, lang))
+            })
+            .collect();
+        let mut languages: Vec<lingua::Language> = shared
+            .iter()
+            .filter(|(_, lang)| langs.contains(lang))
+            .map(|(language, _)| *language)
+            .collect();
+        if languages.is_empty() {
+            languages = shared.into_iter().map(|(language, _)| language).collect();
+        }
+        LinguaDetector {
+            detector: lingua::LanguageDetectorBuilder::from_languages(&languages).build(),
+        }
+    }
+}
+
+#[cfg(feature = "lingua")]
+impl LanguageDetector for LinguaDetector {
+    fn detect(&self, text: &str) -> Option<(Lang, f64)> {
+        let (language, confidence) = self
+            .detector
+            .compute_language_confidence_values(text)
+            .into_iter()
+            .next()?;
+        str_to_lang(&language.iso_code_639_3().to_string()).map(|lang| (lang, confidence))
+    }
+}
+
+/// Language detection library used by a job
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DetectorBackend {
+    Whatlang,
+    #[cfg(feature = "lingua")]
+    Lingua,
+}
+
+impl DetectorBackend {
+    /// `None` for unknown names and for backends left out of the build
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "whatlang" => Some(Self::Whatlang),
+            #[cfg(feature = "lingua")]
+            "lingua" => Some(Self::Lingua),
+            _ => None,
+        }
+    }
+}
+
+/// Detector choosing between `langs`, or between every language the backend knows when empty
+pub fn build_langdetector(backend: DetectorBackend, langs: Vec<&str>) -> Box<dyn LanguageDetector> {
     let langs = lang_builder(langs);
+    match backend {
+        DetectorBackend::Whatlang if langs.is_empty() => Box::new(Detector::new()),
+        DetectorBackend::Whatlang => Box::new(Detector::with_allowlist(langs)),
+        #[cfg(feature = "lingua")]
+        DetectorBackend::Lingua => Box::new(LinguaDetector::new(&langs)),
+    }
+}
+
+/// Decides from its `LanguageProfile` whether a page is in an accepted language
+pub struct LanguagePolicy {
+    accept: Vec<Lang>,
+    min_share: f64,
+    min_confidence: f64,
+}
 
-    Detector::with_allowlist(langs)
+impl LanguagePolicy {
+    pub fn new(accept: Vec<Lang>, min_share: f64, min_confidence: f64) -> Self {
+        Self {
+            accept,
+            min_share,
+            min_confidence,
+        }
+    }
+
+    /// Every page is accepted when no language is
+    pub fn accepts(&self, profile: &LanguageProfile) -> bool {
+        self.accept.is_empty() || profile.share_of(&self.accept, self.min_confidence) >= self.min_share
+    }
 }
 
-pub fn has_language(detector: &Detector, text: &str, languages: &[Lang]) -> bool {
-    if let Some(lang) = detector.detect_lang(text) {
-        if languages.contains(&lang) {
-            return true;
+/// Paragraphs are grouped until they hold this many characters before being classified, shorter
+/// texts are too ambiguous for the detector
+const MIN_CHUNK_CHARS: usize = 120;
+
+/// Languages of a text, classified chunk by chunk of its paragraphs
+pub struct LanguageProfile {
+    /// language, detection confidence and character count of every chunk that was classified
+    chunks: Vec<(Lang, f64, usize)>,
+    /// characters of all the chunks, including those no language was detected in
+    total: usize,
+}
+
+impl LanguageProfile {
+    pub fn detect(detector: &dyn LanguageDetector, text: &str) -> Self {
+        let mut chunks = Vec::new();
+        let mut total = 0;
+        for chunk in chunks_of(text) {
+            let len = chunk.chars().count();
+            total += len;
+            if let Some((lang, confidence)) = detector.detect(&chunk) {
+                chunks.push((lang, confidence, len));
+            }
         }
-    };
-    let text_piece = text.split(' ');
-    for txt in text_piece {
-        if let Some(lang) = detector.detect_lang(txt) {
-            if languages.contains(&lang) {
-                return true;
+        LanguageProfile { chunks, total }
+    }
+
+    /// Share of the text in each language, largest first
+    pub fn distribution(&self) -> Vec<(Lang, f64)> {
+        let mut shares: Vec<(Lang, f64)> = Vec::new();
+        for &(lang, _, len) in self.chunks.iter() {
+            let share = len as f64 / self.total as f64;
+            match shares.iter_mut().find(|(known, _)| *known == lang) {
+                Some((_, total)) => *total += share,
+                None => shares.push((lang, share)),
             }
         }
+        shares.sort_by(|a, b| b.1.total_cmp(&a.1));
+        shares
+    }
+
+    /// Mean confidence of the classified chunks weighted by their length
+    pub fn confidence(&self) -> f64 {
+        let classified: usize = self.chunks.iter().map(|&(_, _, len)| len).sum();
+        if classified == 0 {
+            return 0.0;
+        }
+        self.chunks
+            .iter()
+            .map(|&(_, confidence, len)| confidence * len as f64)
+            .sum::<f64>()
+            / classified as f64
+    }
+
+    /// Share of the text detected as one of `languages` with at least `min_confidence`
+    pub fn share_of(&self, languages: &[Lang], min_confidence: f64) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        let matching: usize = self
+            .chunks
+            .iter()
+            .filter(|&&(lang, confidence, _)| languages.contains(&lang) && confidence >= min_confidence)
+            .map(|&(_, _, len)| len)
+            .sum();
+        matching as f64 / self.total as f64
+    }
+}
+
+fn chunks_of(text: &str) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut chunk = String::new();
+    for line in text.lines().filter(|line| line.chars().any(char::is_alphabetic)) {
+        if !chunk.is_empty() {
+            chunk.push(' ');
+        }
+        chunk.push_str(line.trim());
+        if chunk.chars().count() >= MIN_CHUNK_CHARS {
+            chunks.push(std::mem::take(&mut chunk));
+        }
+    }
+    // a short tail joins the previous chunk rather than being classified alone
+    match chunks.last_mut() {
+        Some(last) if !chunk.is_empty() => {
+            last.push(' ');
+            last.push_str(&chunk);
+        }
+        _ if !chunk.is_empty() => chunks.push(chunk),
+        _ => {}
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENGLISH: &str = "The library opened a new reading room this week, with long tables, quiet corners and \
+                           shelves of books on history, science and the arts for every visitor.";
+    const ARABIC: &str = "افتتحت المكتبة هذا الأسبوع قاعة جديدة للقراءة تضم طاولات طويلة وأركانا هادئة ورفوفا \
+                          من الكتب في التاريخ والعلوم والفنون لكل زائر يأتي إليها.";
+
+    /// Tells Arabic script from the rest, with a fixed confidence
+    struct ScriptDetector(f64);
+
+    impl LanguageDetector for ScriptDetector {
+        fn detect(&self, text: &str) -> Option<(Lang, f64)> {
+            let arabic = text.chars().any(|c| ('\u{0600}'..='\u{06ff}').contains(&c));
+            Some((if arabic { Lang::Ara } else { Lang::Eng }, self.0))
+        }
+    }
+
+    fn arabic_policy(min_share: f64, min_confidence: f64) -> LanguagePolicy {
+        LanguagePolicy::new(vec![Lang::Ara], min_share, min_confidence)
+    }
+
+    #[test]
+    fn codes_and_names_of_languages() {
+        let cases = [
+            ("ar", Some(Lang::Ara)),
+            ("ara", Some(Lang::Ara)),
+            ("Arabic", Some(Lang::Ara)),
+            (" AR ", Some(Lang::Ara)),
+            ("zh", Some(Lang::Cmn)),
+            ("no", Some(Lang::Nob)),
+            ("nor", Some(Lang::Nob)),
+            ("xx", None),
+            ("klingon", None),
+        ];
+        for (name, lang) in cases {
+            assert_eq!(str_to_lang(name), lang, "{name}");
+        }
+    }
+
+    #[test]
+    fn english_page_with_arabic_footer_is_rejected() {
+        let text = [ENGLISH, ENGLISH, ENGLISH, ARABIC].join("\n");
+        let profile = LanguageProfile::detect(&Detector::new(), &text);
+        assert_eq!(profile.distribution()[0].0, Lang::Eng);
+        assert!(!arabic_policy(0.5, 0.5).accepts(&profile));
+    }
+
+    #[test]
+    fn arabic_page_is_accepted() {
+        let text = [ARABIC, ARABIC, ARABIC, ENGLISH].join("\n");
+        let profile = LanguageProfile::detect(&Detector::new(), &text);
+        assert_eq!(profile.distribution()[0].0, Lang::Ara);
+        assert!(arabic_policy(0.5, 0.5).accepts(&profile));
+    }
+
+    #[test]
+    fn text_without_letters_has_no_share() {
+        for text in ["", "12 345 -- 67.8\n***"] {
+            let profile = LanguageProfile::detect(&ScriptDetector(1.0), text);
+            assert_eq!(profile.share_of(&[Lang::Ara], 0.0), 0.0);
+            assert_eq!(profile.confidence(), 0.0);
+            assert!(!arabic_policy(0.5, 0.5).accepts(&profile));
+        }
+    }
+
+    #[test]
+    fn min_share_threshold() {
+        let text = [ARABIC, ENGLISH].join("\n");
+        let profile = LanguageProfile::detect(&ScriptDetector(1.0), &text);
+        let share = profile.share_of(&[Lang::Ara], 0.0);
+        assert!(share > 0.4 && share < 0.6);
+        assert!(arabic_policy(0.4, 0.5).accepts(&profile));
+        assert!(!arabic_policy(0.6, 0.5).accepts(&profile));
+    }
+
+    #[test]
+    fn min_confidence_threshold() {
+        let profile = LanguageProfile::detect(&ScriptDetector(0.4), ARABIC);
+        assert_eq!(profile.share_of(&[Lang::Ara], 0.3), 1.0);
+        assert_eq!(profile.share_of(&[Lang::Ara], 0.5), 0.0);
+        assert!(arabic_policy(0.5, 0.3).accepts(&profile));
+        assert!(!arabic_policy(0.5, 0.5).accepts(&profile));
     }
-    false
 }
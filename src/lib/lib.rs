@@ -7,12 +7,28 @@ extern crate core;
 use std::fmt::{Display, Formatter};
 use std::sync::atomic::{AtomicU64, Ordering};
 
+use crate::scope::ScopeRejection;
+
+pub mod canonical;
+pub mod charset;
 pub mod crawl;
 pub mod crawl_utils;
+pub mod digest;
+pub mod document;
+pub mod extract;
+pub mod index;
 pub mod job_config;
 mod lang;
+pub mod links;
+pub mod metadata;
+pub mod mime;
+pub mod quality;
+pub mod reader;
 pub mod response;
 pub mod robots;
+pub mod scope;
+pub mod sink;
+pub mod writer;
 
 pub struct CrawlEntry {
     pub url: String,
@@ -37,9 +53,16 @@ pub struct CrawlCounters {
     initial: AtomicU64,
     extra: AtomicU64,
     queued: AtomicU64,
+    skipped: AtomicU64,
+    /// batches a sink failed to write, the other sinks still got them
+    write_errors: AtomicU64,
+    /// links left out of the crawl, by `ScopeRejection`
+    out_of_scope: [AtomicU64; ScopeRejection::ALL.len()],
 }
 
 impl CrawlCounters {
+    /// Counters starting from the given values, with no skipped or out of scope URLs and no write
+    /// errors
     pub fn new(visited: u64, failed: u64, initial: u64, extra: u64, queued: u64) -> Self {
         Self {
             visited: visited.into(),
@@ -47,6 +70,7 @@ impl CrawlCounters {
             initial: initial.into(),
             extra: extra.into(),
             queued: queued.into(),
+            ..Self::default()
         }
     }
     pub fn increment_visited(&self) {
@@ -55,6 +79,32 @@ impl CrawlCounters {
     pub fn increment_failed(&self) {
         self.failed.fetch_add(1, Ordering::Relaxed);
     }
+    pub fn increment_skipped(&self) {
+        self.skipped.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn increment_write_errors(&self) {
+        self.write_errors.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn increment_out_of_scope(&self, reason: ScopeRejection) {
+        self.out_of_scope[reason as usize].fetch_add(1, Ordering::Relaxed);
+    }
+    /// Number of links left out of the crawl, followed by the number per rule when there are any
+    fn out_of_scope_summary(&self) -> String {
+        let counts: Vec<(&str, u64)> = ScopeRejection::ALL
+            .iter()
+            .map(|reason| (reason.name(), self.out_of_scope[*reason as usize].load(Ordering::Relaxed)))
+            .filter(|(_, count)| *count > 0)
+            .collect();
+        if counts.is_empty() {
+            return "0".to_string();
+        }
+        let total: u64 = counts.iter().map(|(_, count)| count).sum();
+        let reasons: Vec<String> = counts
+            .iter()
+            .map(|(name, count)| format!("{name} {count}"))
+            .collect();
+        format!("{total} ({})", reasons.join(", "))
+    }
     pub fn increment_queued(&self) {
         self.queued.fetch_add(1, Ordering::Relaxed);
     }
@@ -68,6 +118,7 @@ impl CrawlCounters {
             "extra" => self.extra.fetch_add(value, Ordering::Relaxed),
             "queued" => self.queued.fetch_add(value, Ordering::Relaxed),
             "initial" => self.initial.fetch_add(value, Ordering::Relaxed),
+            "skipped" => self.skipped.fetch_add(value, Ordering::Relaxed),
             _ => 0,
         };
     }
@@ -75,11 +126,17 @@ impl CrawlCounters {
         format!(
             "Visited : {}\n\
             Failed : {}\n\
+            Skipped : {}\n\
+            Out of Scope : {}\n\
+            Write Errors : {}\n\
             Extra Extracted : {}\n\
             Links in Queue : {}\n\
             <===========================================================>\n",
             self.visited.load(Ordering::Relaxed),
             self.failed.load(Ordering::Relaxed),
+            self.skipped.load(Ordering::Relaxed),
+            self.out_of_scope_summary(),
+            self.write_errors.load(Ordering::Relaxed),
             self.extra.load(Ordering::Relaxed),
             self.queued.load(Ordering::Relaxed)
         )
@@ -92,12 +149,18 @@ impl Display for CrawlCounters {
             f,
             "Visited : {}\n\
             Failed : {}\n\
+            Skipped : {}\n\
+            Out of Scope : {}\n\
+            Write Errors : {}\n\
             Extra Extracted : {}\n\
             Links in Queue : {}\n\
             Initial Seeds : {}\n\
             <<<<<<<<<<<<<<<<<<<<TOTAL>>>>>>>>>>>>>>>>>>>>\n",
             self.visited.load(Ordering::Relaxed),
             self.failed.load(Ordering::Relaxed),
+            self.skipped.load(Ordering::Relaxed),
+            self.out_of_scope_summary(),
+            self.write_errors.load(Ordering::Relaxed),
             self.extra.load(Ordering::Relaxed),
             self.queued.load(Ordering::Relaxed),
             self.initial.load(Ordering::Relaxed)
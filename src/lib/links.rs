@@ -0,0 +1,277 @@
+use soup::{NodeExt, QueryBuilderExt, Soup};
+use url::Url;
+
+/// Sources followed when the job does not set `link_sources`: navigation, frames and redirects.
+/// Translations, feeds and sitemaps are left out, the XML of feeds and sitemaps is not in
+/// `mime::DEFAULT_ALLOW` either
+pub const DEFAULT_SOURCES: &[&str] = &[
+    "a",
+    "area",
+    "iframe",
+    "frame",
+    "next",
+    "prev",
+    "canonical",
+    "refresh",
+    "pdf",
+];
+
+/// Feed types announced by `<link rel=alternate>`
+const FEED_TYPES: &[&str] = &[
+    "application/rss+xml",
+    "application/atom+xml",
+    "application/feed+json",
+    "application/json+feed",
+];
+
+/// Element or attribute an outlink was found in
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LinkSource {
+    /// `<a href>`
+    Anchor,
+    /// `<area href>` of an image map
+    Area,
+    /// `<iframe src>`
+    Iframe,
+    /// `<frame src>`
+    Frame,
+    /// `<link rel=alternate>` that is not a feed, translations and other formats of the page
+    Alternate,
+    /// `<link rel=next>`
+    Next,
+    /// `<link rel=prev>` or `<link rel=previous>`
+    Prev,
+    /// `<link rel=canonical>`
+    Canonical,
+    /// URL of a `<meta http-equiv=refresh>`
+    Refresh,
+    /// `<link rel=alternate>` with an RSS, Atom or JSON feed type
+    Feed,
+    /// `<link rel=sitemap>`
+    Sitemap,
+    /// link annotation of a PDF
+    Pdf,
+}
+
+impl LinkSource {
+    pub const ALL: &'static [LinkSource] = &[
+        Self::Anchor,
+        Self::Area,
+        Self::Iframe,
+        Self::Frame,
+        Self::Alternate,
+        Self::Next,
+        Self::Prev,
+        Self::Canonical,
+        Self::Refresh,
+        Self::Feed,
+        Self::Sitemap,
+        Self::Pdf,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|source| source.name() == name.to_lowercase())
+    }
+
+    /// Name of the source in `link_sources` and in `metadata` records
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Anchor => "a",
+            Self::Area => "area",
+            Self::Iframe => "iframe",
+            Self::Frame => "frame",
+            Self::Alternate => "alternate",
+            Self::Next => "next",
+            Self::Prev => "prev",
+            Self::Canonical => "canonical",
+            Self::Refresh => "refresh",
+            Self::Feed => "feed",
+            Self::Sitemap => "sitemap",
+            Self::Pdf => "pdf",
+        }
+    }
+}
+
+/// Absolute outlink and where it was found
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PageLink {
+    pub url: String,
+    pub source: LinkSource,
+    /// the element has `rel="nofollow"`
+    pub nofollow: bool,
+}
+
+impl PageLink {
+    pub fn new(url: String, source: LinkSource) -> Self {
+        Self {
+            url,
+            source,
+            nofollow: false,
+        }
+    }
+}
+
+/// Links of the page coming from one of `sources`, in document order per source, resolved
+/// against its `<base href>` or against `page_url`, the final URL of the response, when it has
+/// none. Links that cannot be resolved are dropped, those of any scheme are kept for
+/// `CrawlScope::check` to count
+pub fn page_links(soup: &Soup, page_url: &str, sources: &[LinkSource]) -> Vec<PageLink> {
+    let mut links = vec![];
+    let Ok(page_url) = Url::parse(page_url) else {
+        return links;
+    };
+    let base = soup
+        .tag("base")
+        .find_all()
+        .find_map(|base| base.get("href"))
+        .and_then(|href| page_url.join(href.trim()).ok())
+        .unwrap_or(page_url);
+    let mut push = |href: &str, source: LinkSource, nofollow: bool| {
+        if !sources.contains(&source) {
+            return;
+        }
+        if let Ok(href) = base.join(href.trim()) {
+            links.push(PageLink {
+                url: String::from(href),
+                source,
+                nofollow,
+            });
+        }
+    };
+    for (tag, attribute, source) in [
+        ("a", "href", LinkSource::Anchor),
+        ("area", "href", LinkSource::Area),
+        ("iframe", "src", LinkSource::Iframe),
+        ("frame", "src", LinkSource::Frame),
+    ] {
+        for element in soup.tag(tag).find_all() {
+            if let Some(href) = element.get(attribute) {
+                push(&href, source, has_nofollow(element.get("rel").as_deref()));
+            }
+        }
+    }
+    for link in soup.tag("link").find_all() {
+        let (Some(rel), Some(href)) = (link.get("rel"), link.get("href")) else {
+            continue;
+        };
+        let nofollow = has_nofollow(Some(&rel));
+        let rel = rel.to_lowercase();
+        for relation in rel.split_whitespace() {
+            let source = match relation {
+                "alternate" => {
+                    let is_feed = link.get("type").is_some_and(|kind| {
+                        FEED_TYPES.contains(&kind.trim().to_lowercase().as_str())
+                    });
+                    if is_feed {
+                        LinkSource::Feed
+                    } else {
+                        LinkSource::Alternate
+                    }
+                }
+                "next" => LinkSource::Next,
+                "prev" | "previous" => LinkSource::Prev,
+                "canonical" => LinkSource::Canonical,
+                "sitemap" => LinkSource::Sitemap,
+                _ => continue,
+            };
+            push(&href, source, nofollow);
+        }
+    }
+    for meta in soup.tag("meta").find_all() {
+        if meta
+            .get("http-equiv")
+            .is_some_and(|equiv| equiv.trim().eq_ignore_ascii_case("refresh"))
+            && let Some(url) = meta.get("content").as_deref().and_then(refresh_url)
+        {
+            push(url, LinkSource::Refresh, false);
+        }
+    }
+    links
+}
+
+fn has_nofollow(rel: Option<&str>) -> bool {
+    rel.is_some_and(|rel| {
+        rel.split_whitespace()
+            .any(|relation| relation.eq_ignore_ascii_case("nofollow"))
+    })
+}
+
+/// Target of a `refresh` value such as `5; url='/next.html'`, `None` when it only reloads the page
+fn refresh_url(content: &str) -> Option<&str> {
+    let (_, target) = content.split_once([';', ','])?;
+    let target = target.trim_start();
+    let target = match target.get(..3) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("url") => {
+            target[3..].trim_start().strip_prefix('=').unwrap_or(target)
+        }
+        _ => target,
+    };
+    let target = target.trim().trim_matches(['\'', '"']).trim();
+    (!target.is_empty()).then_some(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refresh_targets() {
+        assert_eq!(refresh_url("5; url=/next.html"), Some("/next.html"));
+        assert_eq!(
+            refresh_url("0;URL='http://example.com/a'"),
+            Some("http://example.com/a")
+        );
+        assert_eq!(
+            refresh_url("3; url = \"/quoted page\""),
+            Some("/quoted page")
+        );
+        assert_eq!(refresh_url("0, next.html"), Some("next.html"));
+        assert_eq!(refresh_url("1; /plain"), Some("/plain"));
+    }
+
+    #[test]
+    fn refresh_without_target() {
+        assert_eq!(refresh_url("30"), None);
+        assert_eq!(refresh_url("5; url="), None);
+        assert_eq!(refresh_url("5; url=''"), None);
+    }
+
+    #[test]
+    fn links_by_source() {
+        let soup = Soup::new(
+            "<html><head><base href='http://example.com/dir/'>\
+             <link rel='next' href='page2'>\
+             <link rel='alternate' type='application/rss+xml' href='/feed'>\
+             <link rel='alternate' hreflang='fr' href='/fr/'>\
+             <meta http-equiv='Refresh' content='10; url=later'></head>\
+             <body><a href='a' rel='nofollow'>a</a><area href='/map'>\
+             <iframe src='frame.html'></iframe></body></html>",
+        );
+        let links = page_links(&soup, "http://other.org/page", LinkSource::ALL);
+        let found: Vec<(&str, LinkSource, bool)> = links
+            .iter()
+            .map(|link| (link.url.as_str(), link.source, link.nofollow))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("http://example.com/dir/a", LinkSource::Anchor, true),
+                ("http://example.com/map", LinkSource::Area, false),
+                (
+                    "http://example.com/dir/frame.html",
+                    LinkSource::Iframe,
+                    false
+                ),
+                ("http://example.com/dir/page2", LinkSource::Next, false),
+                ("http://example.com/feed", LinkSource::Feed, false),
+                ("http://example.com/fr/", LinkSource::Alternate, false),
+                ("http://example.com/dir/later", LinkSource::Refresh, false),
+            ]
+        );
+        let anchors = page_links(&soup, "http://other.org/page", &[LinkSource::Anchor]);
+        assert_eq!(anchors.len(), 1);
+    }
+}
@@ -0,0 +1,117 @@
+use itertools::Itertools;
+use soup::{NodeExt, QueryBuilderExt, Soup};
+use url::Url;
+
+use crate::crawl_utils;
+
+/// `<title>` of the page a conversion record was extracted from
+pub const TITLE_HEADER: &str = "warc-page-title";
+
+/// `<meta name="description">`
+pub const DESCRIPTION_HEADER: &str = "warc-page-description";
+
+/// `<meta name="keywords">`
+pub const KEYWORDS_HEADER: &str = "warc-page-keywords";
+
+/// `lang` attribute of `<html>`
+pub const LANG_HEADER: &str = "warc-page-lang";
+
+/// `Content-Language` header of the response
+pub const CONTENT_LANGUAGE_HEADER: &str = "warc-page-content-language";
+
+/// Absolute URL of `<link rel="canonical">`
+pub const CANONICAL_HEADER: &str = "warc-page-canonical";
+
+/// `article:published_time` or `og:published_time`
+pub const PUBLISHED_HEADER: &str = "warc-page-published";
+
+/// `article:modified_time` or `og:updated_time`
+pub const MODIFIED_HEADER: &str = "warc-page-modified";
+
+/// Cataloguing metadata of a page, every field is `None` when missing or empty
+#[derive(Debug, Default, Clone)]
+pub struct PageMetadata {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub keywords: Option<String>,
+    pub lang: Option<String>,
+    pub content_language: Option<String>,
+    pub canonical: Option<String>,
+    pub published: Option<String>,
+    pub modified: Option<String>,
+}
+
+impl PageMetadata {
+    /// Reads the metadata of the page at `url`, `content_language` is its `Content-Language` header
+    pub fn from_soup(soup: &Soup, url: &str, content_language: Option<&str>) -> Self {
+        PageMetadata {
+            title: crawl_utils::soup_title(soup),
+            description: meta_content(soup, "name", &["description"]),
+            keywords: meta_content(soup, "name", &["keywords"]),
+            lang: soup
+                .tag("html")
+                .find()
+                .and_then(|html| html.get("lang"))
+                .and_then(|lang| collapse(&lang)),
+            content_language: content_language.and_then(collapse),
+            canonical: canonical(soup, url),
+            published: meta_content(
+                soup,
+                "property",
+                &["article:published_time", "og:published_time"],
+            ),
+            modified: meta_content(soup, "property", &["article:modified_time", "og:updated_time"]),
+        }
+    }
+
+    /// Header names and values of the fields that are present
+    pub fn headers(&self) -> Vec<(&'static str, &str)> {
+        [
+            (TITLE_HEADER, &self.title),
+            (DESCRIPTION_HEADER, &self.description),
+            (KEYWORDS_HEADER, &self.keywords),
+            (LANG_HEADER, &self.lang),
+            (CONTENT_LANGUAGE_HEADER, &self.content_language),
+            (CANONICAL_HEADER, &self.canonical),
+            (PUBLISHED_HEADER, &self.published),
+            (MODIFIED_HEADER, &self.modified),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| value.as_deref().map(|value| (name, value)))
+        .collect()
+    }
+}
+
+/// `content` of the first `<meta>` whose `attribute` is one of `names`, in the order of `names`
+fn meta_content(soup: &Soup, attribute: &str, names: &[&str]) -> Option<String> {
+    let metas = soup.tag("meta").find_all().collect_vec();
+    names.iter().find_map(|name| {
+        metas
+            .iter()
+            .filter(|meta| {
+                meta.get(attribute)
+                    .is_some_and(|value| value.trim().eq_ignore_ascii_case(name))
+            })
+            .find_map(|meta| meta.get("content").and_then(|content| collapse(&content)))
+    })
+}
+
+fn canonical(soup: &Soup, url: &str) -> Option<String> {
+    let href = soup.tag("link").find_all().find_map(|link| {
+        let canonical = link.get("rel").is_some_and(|rel| {
+            rel.split_whitespace()
+                .any(|rel| rel.eq_ignore_ascii_case("canonical"))
+        });
+        canonical.then(|| link.get("href")).flatten()
+    })?;
+    Url::parse(url)
+        .and_then(|base| base.join(href.trim()))
+        .ok()
+        .map(String::from)
+}
+
+/// Whitespace runs collapsed to single spaces so the value fits on a header line
+fn collapse(value: &str) -> Option<String> {
+    let value = value.split_whitespace().join(" ");
+    (!value.is_empty()).then_some(value)
+}
@@ -0,0 +1,263 @@
+use url::Url;
+
+/// Types fetched when the job does not set `mime_allow`: pages, plain text and the documents
+/// `document::DocumentKind` extracts text from
+pub const DEFAULT_ALLOW: &[&str] = &[
+    "text/html",
+    "application/xhtml+xml",
+    "text/plain",
+    "application/pdf",
+    "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+    "application/vnd.oasis.opendocument.text",
+];
+
+/// Number of payload bytes looked at when the server sends no `Content-Type`
+pub const SNIFF_LEN: usize = 512;
+
+/// Which content types are downloaded. Patterns are `type/subtype` where either side may be `*`,
+/// a type matching `deny` is always skipped and an empty `allow` accepts every other type
+#[derive(Clone, Debug, Default)]
+pub struct MimePolicy {
+    allow: Vec<String>,
+    deny: Vec<String>,
+}
+
+impl MimePolicy {
+    pub fn new(allow: Vec<String>, deny: Vec<String>) -> Self {
+        let lowercase = |patterns: Vec<String>| {
+            patterns
+                .into_iter()
+                .map(|pattern| pattern.trim().to_lowercase())
+                .collect()
+        };
+        Self {
+            allow: lowercase(allow),
+            deny: lowercase(deny),
+        }
+    }
+
+    /// `Err` holds the reason `mime` is skipped
+    pub fn check(&self, mime: &str) -> Result<(), String> {
+        let mime = essence(mime);
+        if self.deny.iter().any(|pattern| matches(pattern, &mime)) {
+            return Err(format!("content type {mime} is denied"));
+        }
+        if !self.allow.is_empty() && !self.allow.iter().any(|pattern| matches(pattern, &mime)) {
+            return Err(format!("content type {mime} is not allowed"));
+        }
+        Ok(())
+    }
+
+    /// Checks the type the extension of `url` stands for, so links to images, archives or media
+    /// are skipped before being requested. URLs without a known extension pass
+    pub fn check_url(&self, url: &str) -> Result<(), String> {
+        match extension_mime(url) {
+            Some((extension, mime)) => self
+                .check(mime)
+                .map_err(|reason| format!("extension .{extension}, {reason}")),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Whether `pattern` is a valid `mime_allow` or `mime_deny` entry
+pub fn valid_pattern(pattern: &str) -> bool {
+    pattern
+        .split_once('/')
+        .is_some_and(|(kind, subtype)| !kind.is_empty() && !subtype.is_empty() && !subtype.contains('/'))
+}
+
+/// Type and subtype of a `Content-Type` value, without its parameters
+pub fn essence(content_type: &str) -> String {
+    content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase()
+}
+
+fn matches(pattern: &str, mime: &str) -> bool {
+    let (Some((kind, subtype)), Some((mime_kind, mime_subtype))) =
+        (pattern.split_once('/'), mime.split_once('/'))
+    else {
+        return false;
+    };
+    (kind == "*" || kind == mime_kind) && (subtype == "*" || subtype == mime_subtype)
+}
+
+/// Type of a payload from its first bytes, for responses sent without a `Content-Type`
+pub fn sniff(prefix: &[u8]) -> &'static str {
+    const SIGNATURES: &[(&[u8], &str)] = &[
+        (b"%PDF-", "application/pdf"),
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"BM", "image/bmp"),
+        (b"\x00\x00\x01\x00", "image/x-icon"),
+        (b"wOFF", "font/woff"),
+        (b"wOF2", "font/woff2"),
+        (b"ID3", "audio/mpeg"),
+        (b"OggS", "audio/ogg"),
+        (b"fLaC", "audio/flac"),
+        (b"\x1a\x45\xdf\xa3", "video/webm"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"\x1f\x8b", "application/gzip"),
+        (b"Rar!\x1a\x07", "application/vnd.rar"),
+        (b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+        (b"BZh", "application/x-bzip2"),
+        (b"\xfd7zXZ\x00", "application/x-xz"),
+        (b"MZ", "application/vnd.microsoft.portable-executable"),
+        (b"\x7fELF", "application/x-elf"),
+    ];
+    if let Some((_, mime)) = SIGNATURES
+        .iter()
+        .find(|(signature, _)| prefix.starts_with(signature))
+    {
+        return mime;
+    }
+    if prefix.len() >= 12 && prefix.starts_with(b"RIFF") {
+        return match &prefix[8..12] {
+            b"WEBP" => "image/webp",
+            b"WAVE" => "audio/wav",
+            b"AVI " => "video/x-msvideo",
+            _ => "application/octet-stream",
+        };
+    }
+    if prefix.len() >= 8 && &prefix[4..8] == b"ftyp" {
+        return "video/mp4";
+    }
+    let start = String::from_utf8_lossy(prefix).trim_start().to_lowercase();
+    if ["<!doctype html", "<html", "<head", "<body", "<!--", "<p", "<div", "<title"]
+        .iter()
+        .any(|tag| start.starts_with(tag))
+    {
+        return "text/html";
+    }
+    if start.starts_with("<?xml") {
+        return "application/xml";
+    }
+    // control characters other than whitespace only appear in binary formats
+    if prefix
+        .iter()
+        .any(|byte| byte.is_ascii_control() && !byte.is_ascii_whitespace() && *byte != 0x0c)
+    {
+        "application/octet-stream"
+    } else {
+        "text/plain"
+    }
+}
+
+/// Extension of the last path segment of `url` and the type it stands for
+fn extension_mime(url: &str) -> Option<(String, &'static str)> {
+    let url = Url::parse(url).ok()?;
+    let segment = url.path_segments()?.next_back()?.to_lowercase();
+    let (_, extension) = segment.rsplit_once('.')?;
+    let mime = match extension {
+        "html" | "htm" | "shtml" | "php" | "asp" | "aspx" | "jsp" => "text/html",
+        "xhtml" => "application/xhtml+xml",
+        "txt" => "text/plain",
+        "pdf" => "application/pdf",
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "odt" => "application/vnd.oasis.opendocument.text",
+        "doc" => "application/msword",
+        "xls" => "application/vnd.ms-excel",
+        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        "ppt" => "application/vnd.ms-powerpoint",
+        "pptx" => "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+        "css" => "text/css",
+        "js" | "mjs" => "text/javascript",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "rss" => "application/rss+xml",
+        "atom" => "application/atom+xml",
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "bmp" => "image/bmp",
+        "tif" | "tiff" => "image/tiff",
+        "avif" => "image/avif",
+        "mp3" => "audio/mpeg",
+        "ogg" | "oga" => "audio/ogg",
+        "wav" => "audio/wav",
+        "flac" => "audio/flac",
+        "m4a" => "audio/mp4",
+        "mp4" | "m4v" => "video/mp4",
+        "webm" => "video/webm",
+        "avi" => "video/x-msvideo",
+        "mov" => "video/quicktime",
+        "mkv" => "video/x-matroska",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "zip" => "application/zip",
+        "gz" | "tgz" => "application/gzip",
+        "bz2" => "application/x-bzip2",
+        "xz" => "application/x-xz",
+        "7z" => "application/x-7z-compressed",
+        "rar" => "application/vnd.rar",
+        "tar" => "application/x-tar",
+        "exe" | "msi" => "application/vnd.microsoft.portable-executable",
+        "dmg" => "application/x-apple-diskimage",
+        "iso" => "application/x-iso9660-image",
+        "apk" => "application/vnd.android.package-archive",
+        "deb" => "application/vnd.debian.binary-package",
+        "rpm" => "application/x-rpm",
+        "bin" => "application/octet-stream",
+        _ => return None,
+    };
+    Some((extension.to_string(), mime))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(allow: &[&str], deny: &[&str]) -> MimePolicy {
+        let owned =
+            |patterns: &[&str]| patterns.iter().map(|pattern| pattern.to_string()).collect();
+        MimePolicy::new(owned(allow), owned(deny))
+    }
+
+    #[test]
+    fn payloads_without_content_type_are_sniffed() {
+        assert_eq!(sniff(b"  <!DOCTYPE html><html><body>page"), "text/html");
+        assert_eq!(sniff(b"<p>a fragment</p>"), "text/html");
+        assert_eq!(sniff(b"%PDF-1.7\n%\xe2\xe3\xcf\xd3"), "application/pdf");
+        assert_eq!(sniff(b"\x00\x01\x02\x03binary"), "application/octet-stream");
+        assert_eq!(sniff(b"just some words\n"), "text/plain");
+        let default = policy(DEFAULT_ALLOW, &[]);
+        assert!(default.check(sniff(b"%PDF-1.4")).is_ok());
+        assert!(default.check(sniff(b"\x7fELF\x02\x01")).is_err());
+    }
+
+    #[test]
+    fn wildcard_patterns() {
+        let texts = policy(&["text/*"], &["text/css"]);
+        assert!(texts.check("text/html; charset=UTF-8").is_ok());
+        assert!(texts.check("TEXT/Plain").is_ok());
+        assert!(texts.check("text/css").is_err());
+        assert!(texts.check("application/pdf").is_err());
+        let no_images = policy(&[], &["image/*", "*/zip"]);
+        assert!(no_images.check("application/pdf").is_ok());
+        assert!(no_images.check("image/png").is_err());
+        assert!(no_images.check("application/zip").is_err());
+        assert!(policy(&["*/*"], &[]).check("video/mp4").is_ok());
+    }
+
+    #[test]
+    fn extensions_are_checked_before_fetching() {
+        let default = policy(DEFAULT_ALLOW, &[]);
+        let zip = default.check_url("http://example.com/files/archive.ZIP?download=1");
+        assert!(zip.unwrap_err().starts_with("extension .zip"));
+        assert!(default.check_url("http://example.com/video.mp4").is_err());
+        assert!(default.check_url("http://example.com/report.pdf").is_ok());
+        assert!(default.check_url("http://example.com/page").is_ok());
+        assert!(default.check_url("http://example.com/v1.2/").is_ok());
+    }
+}
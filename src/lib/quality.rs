@@ -0,0 +1,278 @@
+use std::collections::HashSet;
+
+use config::{Config, Value};
+
+/// Comma separated names of the quality checks a conversion record failed with the `flag` action
+pub const QUALITY_HEADER: &str = "warc-quality-flags";
+
+/// Characters a line of prose ends with
+const TERMINAL_PUNCTUATION: &[char] = &[
+    '.', '!', '?', '…', '"', '\'', '”', '’', '»', ')', ':', '؟', '۔', '।', '。', '！', '？',
+];
+
+/// What happens to a page failing a check
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QualityAction {
+    /// its text record is not written and its links are not followed
+    Drop,
+    /// its text record gets the check in its `WARC-Quality-Flags` header
+    Flag,
+    /// its text record is written but its links are not followed
+    NoFollow,
+}
+
+impl QualityAction {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "drop" => Some(Self::Drop),
+            "flag" => Some(Self::Flag),
+            "nofollow" => Some(Self::NoFollow),
+            _ => None,
+        }
+    }
+}
+
+/// Measures of the extracted text a page is judged on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QualityCheck {
+    /// fewer characters than the threshold
+    MinLength,
+    /// more characters than the threshold
+    MaxLength,
+    /// letters make up less than the threshold of the characters that are not whitespace
+    MinLetterRatio,
+    /// lines ending with punctuation make up less than the threshold of the lines
+    MinPunctuationLines,
+    /// lines repeating an earlier line make up more than the threshold of the lines
+    MaxRepeatedLines,
+}
+
+impl QualityCheck {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "min_length" => Some(Self::MinLength),
+            "max_length" => Some(Self::MaxLength),
+            "min_letter_ratio" => Some(Self::MinLetterRatio),
+            "min_punctuation_lines" => Some(Self::MinPunctuationLines),
+            "max_repeated_lines" => Some(Self::MaxRepeatedLines),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::MinLength => "min_length",
+            Self::MaxLength => "max_length",
+            Self::MinLetterRatio => "min_letter_ratio",
+            Self::MinPunctuationLines => "min_punctuation_lines",
+            Self::MaxRepeatedLines => "max_repeated_lines",
+        }
+    }
+
+    fn fails(&self, stats: &TextStats, threshold: f64) -> bool {
+        match self {
+            Self::MinLength => (stats.length as f64) < threshold,
+            Self::MaxLength => (stats.length as f64) > threshold,
+            Self::MinLetterRatio => stats.letter_ratio < threshold,
+            Self::MinPunctuationLines => stats.punctuation_lines < threshold,
+            Self::MaxRepeatedLines => stats.repeated_lines > threshold,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct QualityFilter {
+    pub check: QualityCheck,
+    pub threshold: f64,
+    pub action: QualityAction,
+}
+
+/// Outcome of the quality filters for one page
+#[derive(Debug, Default)]
+pub struct QualityVerdict {
+    pub drop: bool,
+    pub nofollow: bool,
+    /// checks failed with the `flag` action
+    pub flags: Vec<&'static str>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct QualityFilters {
+    filters: Vec<QualityFilter>,
+}
+
+impl QualityFilters {
+    pub fn new(filters: Vec<QualityFilter>) -> Self {
+        Self { filters }
+    }
+
+    /// Filters of the `quality_filters` job table, a `{ value, action }` table per check
+    pub fn from_job(job: &Config) -> Self {
+        let mut filters: Vec<QualityFilter> = job
+            .get_table("quality_filters")
+            .unwrap()
+            .into_iter()
+            .filter_map(|(name, filter)| parse_filter(&name, filter))
+            .collect();
+        filters.sort_by_key(|filter| filter.check.name());
+        Self::new(filters)
+    }
+
+    pub fn evaluate(&self, text: &str) -> QualityVerdict {
+        let mut verdict = QualityVerdict::default();
+        if self.filters.is_empty() {
+            return verdict;
+        }
+        let stats = TextStats::of(text);
+        for filter in self.filters.iter() {
+            if !filter.check.fails(&stats, filter.threshold) {
+                continue;
+            }
+            match filter.action {
+                QualityAction::Drop => verdict.drop = true,
+                QualityAction::NoFollow => verdict.nofollow = true,
+                QualityAction::Flag => verdict.flags.push(filter.check.name()),
+            }
+        }
+        verdict
+    }
+}
+
+/// Whether `value` is a valid `quality_filters` table
+pub fn valid_filters(value: &Value) -> bool {
+    value.clone().into_table().is_ok_and(|table| {
+        table
+            .into_iter()
+            .all(|(name, filter)| parse_filter(&name, filter).is_some())
+    })
+}
+
+fn parse_filter(name: &str, filter: Value) -> Option<QualityFilter> {
+    let mut filter = filter.into_table().ok()?;
+    if filter.keys().any(|key| key != "value" && key != "action") {
+        return None;
+    }
+    Some(QualityFilter {
+        check: QualityCheck::from_name(name)?,
+        threshold: filter.remove("value")?.into_float().ok()?,
+        action: QualityAction::from_name(&filter.remove("action")?.into_string().ok()?)?,
+    })
+}
+
+struct TextStats {
+    length: usize,
+    letter_ratio: f64,
+    punctuation_lines: f64,
+    repeated_lines: f64,
+}
+
+impl TextStats {
+    fn of(text: &str) -> Self {
+        let (mut letters, mut visible) = (0, 0);
+        for c in text.chars().filter(|c| !c.is_whitespace()) {
+            visible += 1;
+            if c.is_alphabetic() {
+                letters += 1;
+            }
+        }
+        let lines: Vec<&str> = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect();
+        let punctuated = lines
+            .iter()
+            .filter(|line| line.ends_with(TERMINAL_PUNCTUATION))
+            .count();
+        let mut seen = HashSet::new();
+        let repeated = lines.iter().filter(|line| !seen.insert(**line)).count();
+        TextStats {
+            length: text.chars().count(),
+            letter_ratio: ratio(letters, visible),
+            punctuation_lines: ratio(punctuated, lines.len()),
+            repeated_lines: ratio(repeated, lines.len()),
+        }
+    }
+}
+
+fn ratio(part: usize, whole: usize) -> f64 {
+    if whole == 0 {
+        0.0
+    } else {
+        part as f64 / whole as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn verdict(
+        check: QualityCheck,
+        threshold: f64,
+        action: QualityAction,
+        text: &str,
+    ) -> QualityVerdict {
+        QualityFilters::new(vec![QualityFilter {
+            check,
+            threshold,
+            action,
+        }])
+        .evaluate(text)
+    }
+
+    /// Whether `text` fails `check` at `threshold`, reported as a flag
+    fn fails(check: QualityCheck, threshold: f64, text: &str) -> bool {
+        !verdict(check, threshold, QualityAction::Flag, text)
+            .flags
+            .is_empty()
+    }
+
+    #[test]
+    fn length_limits() {
+        assert!(fails(QualityCheck::MinLength, 10.0, "short"));
+        assert!(!fails(QualityCheck::MinLength, 5.0, "short"));
+        assert!(fails(QualityCheck::MaxLength, 4.0, "short"));
+        assert!(!fails(QualityCheck::MaxLength, 5.0, "short"));
+        // characters, not bytes
+        assert!(!fails(QualityCheck::MaxLength, 5.0, "كتابة"));
+    }
+
+    #[test]
+    fn letter_ratio() {
+        assert!(fails(QualityCheck::MinLetterRatio, 0.5, "ab 12 34 56"));
+        assert!(!fails(QualityCheck::MinLetterRatio, 0.5, "abc def 1"));
+    }
+
+    #[test]
+    fn punctuation_lines() {
+        let menu = "Home\nNews\nContact\nA sentence that ends.";
+        assert!(fails(QualityCheck::MinPunctuationLines, 0.5, menu));
+        let prose = "First sentence.\nSecond one!\nهل هذا سؤال؟\nHome";
+        assert!(!fails(QualityCheck::MinPunctuationLines, 0.5, prose));
+    }
+
+    #[test]
+    fn repeated_lines() {
+        let text = "Share\nShare\nShare\nThe article.";
+        assert!(fails(QualityCheck::MaxRepeatedLines, 0.3, text));
+        assert!(!fails(QualityCheck::MaxRepeatedLines, 0.5, text));
+    }
+
+    #[test]
+    fn actions() {
+        let dropped = verdict(QualityCheck::MinLength, 10.0, QualityAction::Drop, "short");
+        assert!(dropped.drop && !dropped.nofollow && dropped.flags.is_empty());
+        let flagged = verdict(QualityCheck::MinLength, 10.0, QualityAction::Flag, "short");
+        assert!(!flagged.drop && flagged.flags == ["min_length"]);
+        let unfollowed = verdict(
+            QualityCheck::MinLength,
+            10.0,
+            QualityAction::NoFollow,
+            "short",
+        );
+        assert!(!unfollowed.drop && unfollowed.nofollow && unfollowed.flags.is_empty());
+        let passed = verdict(QualityCheck::MinLength, 1.0, QualityAction::Drop, "short");
+        assert!(!passed.drop && passed.flags.is_empty());
+    }
+}
@@ -0,0 +1,424 @@
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use data_encoding::HEXLOWER;
+use regex::Regex;
+use serde_json::json;
+use sha1::{Digest, Sha1};
+use thiserror::Error;
+use url::Url;
+use warc::{RawRecordHeader, RecordType, WarcHeader, WarcReader};
+
+use crate::response::{WetRecord, LANGUAGE_HEADER};
+
+type RawRecords = Box<dyn Iterator<Item = Result<(RawRecordHeader, Vec<u8>), warc::Error>>>;
+
+#[derive(Error, Debug)]
+pub enum ReaderError {
+    #[error("Cannot open `{0}`: {1}")]
+    Open(String, io::Error),
+    #[error("Malformed record in `{0}`: {1}")]
+    Record(String, warc::Error),
+    #[error("Invalid URL pattern: {0}")]
+    Pattern(#[from] regex::Error),
+    #[error("Invalid date `{0}`, expected RFC 3339 or YYYY-MM-DD")]
+    Date(String),
+}
+
+/// Conditions a record must meet to be returned, an empty condition accepts every record
+#[derive(Clone, Default)]
+pub struct RecordFilter {
+    url: Option<Regex>,
+    record_types: Vec<RecordType>,
+    languages: Vec<String>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+}
+
+impl RecordFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keeps records whose `WARC-Target-URI` matches `pattern`
+    pub fn url(mut self, pattern: &str) -> Result<Self, ReaderError> {
+        self.url = Some(Regex::new(pattern)?);
+        Ok(self)
+    }
+
+    pub fn record_type(mut self, record_type: &str) -> Self {
+        self.record_types.push(RecordType::from(record_type));
+        self
+    }
+
+    /// Keeps records whose identified content languages include `language`, an ISO 639-3 code
+    pub fn language(mut self, language: &str) -> Self {
+        self.languages.push(language.to_lowercase());
+        self
+    }
+
+    pub fn since(mut self, date: &str) -> Result<Self, ReaderError> {
+        self.since = Some(parse_date(date, false)?);
+        Ok(self)
+    }
+
+    /// Keeps records captured up to `date`, a `YYYY-MM-DD` date includes the whole day
+    pub fn until(mut self, date: &str) -> Result<Self, ReaderError> {
+        self.until = Some(parse_date(date, true)?);
+        Ok(self)
+    }
+
+    pub fn accepts(&self, record: &WetRecord) -> bool {
+        if let Some(pattern) = &self.url
+            && !record
+                .header(WarcHeader::TargetURI)
+                .is_some_and(|url| pattern.is_match(&url))
+        {
+            return false;
+        }
+        if !self.record_types.is_empty()
+            && !record
+                .header(WarcHeader::WarcType)
+                .is_some_and(|record_type| self.record_types.contains(&RecordType::from(record_type)))
+        {
+            return false;
+        }
+        if !self.languages.is_empty()
+            && !record
+                .header(WarcHeader::Unknown(LANGUAGE_HEADER.to_string()))
+                .is_some_and(|languages| {
+                    languages
+                        .split(',')
+                        .any(|language| self.languages.contains(&language.trim().to_lowercase()))
+                })
+        {
+            return false;
+        }
+        if self.since.is_some() || self.until.is_some() {
+            let date = match record
+                .header(WarcHeader::Date)
+                .and_then(|date| DateTime::parse_from_rfc3339(&date).ok())
+            {
+                Some(date) => date.with_timezone(&Utc),
+                None => return false,
+            };
+            if self.since.is_some_and(|since| date < since)
+                || self.until.is_some_and(|until| date > until)
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Iterates the records of a WARC or WET file written by the crawler, gzip compressed or not,
+/// returning those accepted by a `RecordFilter`
+pub struct ArchiveReader {
+    path: String,
+    records: RawRecords,
+    filter: RecordFilter,
+}
+
+impl ArchiveReader {
+    pub fn open(path: &Path, filter: RecordFilter) -> Result<Self, ReaderError> {
+        let display = path.display().to_string();
+        let open_error = |e| ReaderError::Open(display.clone(), e);
+        let records: RawRecords = if is_gzip(path).map_err(open_error)? {
+            Box::new(
+                WarcReader::from_path_gzip(path)
+                    .map_err(open_error)?
+                    .iter_raw_records(),
+            )
+        } else {
+            let file = File::open(path).map_err(open_error)?;
+            Box::new(WarcReader::new(BufReader::new(file)).iter_raw_records())
+        };
+        Ok(Self {
+            path: display,
+            records,
+            filter,
+        })
+    }
+}
+
+impl Iterator for ArchiveReader {
+    type Item = Result<WetRecord, ReaderError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let record = match self.records.next()? {
+                Ok((headers, body)) => WetRecord { headers, body },
+                Err(e) => return Some(Err(ReaderError::Record(self.path.clone(), e))),
+            };
+            if self.filter.accepts(&record) {
+                return Some(Ok(record));
+            }
+        }
+    }
+}
+
+/// Where `export` writes the records it is given
+pub enum ExportFormat {
+    /// record bodies one after the other on stdout
+    Text,
+    /// one JSON object per record on stdout holding its URL, date, type, languages and text
+    Jsonl,
+    /// one text file per record under the directory, at `<host>/<path>/<name>-<timestamp>-<id>.txt`
+    Directory(PathBuf),
+}
+
+pub fn export(
+    records: impl Iterator<Item = WetRecord>,
+    format: &ExportFormat,
+) -> io::Result<u64> {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let mut exported = 0;
+    for record in records {
+        let text = String::from_utf8_lossy(&record.body);
+        match format {
+            ExportFormat::Text => {
+                stdout.write_all(text.as_bytes())?;
+                stdout.write_all(b"\n\n")?;
+            }
+            ExportFormat::Jsonl => {
+                let line = json!({
+                    "url": record.header(WarcHeader::TargetURI),
+                    "date": record.header(WarcHeader::Date),
+                    "type": record.header(WarcHeader::WarcType),
+                    "language": record.header(WarcHeader::Unknown(LANGUAGE_HEADER.to_string())),
+                    "text": text,
+                });
+                writeln!(stdout, "{line}")?;
+            }
+            ExportFormat::Directory(root) => {
+                write_new(&root.join(record_path(&record)), text.as_bytes())?;
+            }
+        }
+        exported += 1;
+    }
+    stdout.flush()?;
+    Ok(exported)
+}
+
+/// Longest file or directory name `record_path` produces for a URL segment, in bytes, well under
+/// the 255 bytes most file systems allow once the timestamp and hash are appended
+const MAX_SEGMENT_LEN: usize = 100;
+
+/// Relative file path of a record in a directory export, built from its URL and date. The name
+/// ends with a short hash of the `WARC-Record-ID`, so captures of the same page in the same second
+/// or with another query string get their own file
+pub(crate) fn record_path(record: &WetRecord) -> PathBuf {
+    let timestamp: String = record
+        .header(WarcHeader::Date)
+        .unwrap_or_default()
+        .chars()
+        .filter(char::is_ascii_digit)
+        .take(14)
+        .collect();
+    let url = record
+        .header(WarcHeader::TargetURI)
+        .and_then(|url| Url::parse(&url).ok());
+    let mut path = PathBuf::new();
+    let mut name = "index".to_string();
+    match url {
+        Some(url) => {
+            path.push(sanitize(url.host_str().unwrap_or("unknown-host")));
+            let segments: Vec<&str> = url.path().split('/').filter(|s| !s.is_empty()).collect();
+            if let Some((last, parents)) = segments.split_last() {
+                for segment in parents {
+                    path.push(sanitize(segment));
+                }
+                if !url.path().ends_with('/') {
+                    name = sanitize(last);
+                } else {
+                    path.push(sanitize(last));
+                }
+            }
+        }
+        None => path.push("unknown-host"),
+    }
+    let id = record
+        .header(WarcHeader::RecordID)
+        .or_else(|| record.header(WarcHeader::TargetURI))
+        .unwrap_or_default();
+    let id = HEXLOWER.encode(&Sha1::digest(id.as_bytes()));
+    path.push(format!("{name}-{timestamp}-{}.txt", &id[..8]));
+    path
+}
+
+/// Writes `contents` to a new file at `path`, failing instead of overwriting an existing one
+pub(crate) fn write_new(path: &Path, contents: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = File::options()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", path.display())))?;
+    file.write_all(contents)
+}
+
+fn sanitize(segment: &str) -> String {
+    let sanitized: String = segment
+        .chars()
+        .map(|c| if c.is_alphanumeric() || "-_.".contains(c) { c } else { '_' })
+        .collect();
+    match sanitized.as_str() {
+        "." | ".." => "_".to_string(),
+        _ if sanitized.len() > MAX_SEGMENT_LEN => {
+            // percent-encoded paths easily exceed the file name limit, the hash keeps
+            // segments sharing a long prefix apart
+            let hash = HEXLOWER.encode(&Sha1::digest(segment.as_bytes()));
+            let mut end = MAX_SEGMENT_LEN - 9;
+            while !sanitized.is_char_boundary(end) {
+                end -= 1;
+            }
+            format!("{}-{}", &sanitized[..end], &hash[..8])
+        }
+        _ => sanitized,
+    }
+}
+
+fn is_gzip(path: &Path) -> io::Result<bool> {
+    let mut file = BufReader::new(File::open(path)?);
+    Ok(file.fill_buf()?.starts_with(&[0x1f, 0x8b]))
+}
+
+/// Instant `date` stands for, the start or with `end_of_day` the end of a plain
+/// `YYYY-MM-DD` day
+fn parse_date(date: &str, end_of_day: bool) -> Result<DateTime<Utc>, ReaderError> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(date) {
+        return Ok(date.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .ok()
+        .and_then(|day| match end_of_day {
+            true => day.and_hms_nano_opt(23, 59, 59, 999_999_999),
+            false => day.and_hms_opt(0, 0, 0),
+        })
+        .map(|time| time.and_utc())
+        .ok_or_else(|| ReaderError::Date(date.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(url: &str, date: &str, id: &str) -> WetRecord {
+        WetRecord {
+            headers: RawRecordHeader {
+                version: "1.1".to_string(),
+                headers: [
+                    (WarcHeader::WarcType, "conversion"),
+                    (WarcHeader::TargetURI, url),
+                    (WarcHeader::Date, date),
+                    (WarcHeader::RecordID, id),
+                ]
+                .into_iter()
+                .map(|(name, value)| (name, value.as_bytes().to_vec()))
+                .collect(),
+            },
+            body: b"text".to_vec(),
+        }
+    }
+
+    #[test]
+    fn parse_rfc3339_dates() {
+        let date = parse_date("2026-10-18T09:15:40+02:00", true).unwrap();
+        assert_eq!(date.to_rfc3339(), "2026-10-18T07:15:40+00:00");
+    }
+
+    #[test]
+    fn parse_plain_days() {
+        let start = parse_date("2026-10-18", false).unwrap();
+        let end = parse_date("2026-10-18", true).unwrap();
+        assert_eq!(start.to_rfc3339(), "2026-10-18T00:00:00+00:00");
+        assert_eq!(end.to_rfc3339(), "2026-10-18T23:59:59.999999999+00:00");
+    }
+
+    #[test]
+    fn parse_invalid_dates() {
+        assert!(matches!(
+            parse_date("18/10/2026", false),
+            Err(ReaderError::Date(_))
+        ));
+        assert!(matches!(
+            parse_date("2026-02-30", true),
+            Err(ReaderError::Date(_))
+        ));
+    }
+
+    #[test]
+    fn until_includes_the_whole_day() {
+        let filter = RecordFilter::new()
+            .since("2026-10-18")
+            .unwrap()
+            .until("2026-10-18")
+            .unwrap();
+        let at = |date| record("http://example.com/", date, "<urn:uuid:1>");
+        assert!(filter.accepts(&at("2026-10-18T00:00:00Z")));
+        assert!(filter.accepts(&at("2026-10-18T23:59:59Z")));
+        assert!(!filter.accepts(&at("2026-10-17T23:59:59Z")));
+        assert!(!filter.accepts(&at("2026-10-19T00:00:00Z")));
+    }
+
+    #[test]
+    fn record_paths_follow_the_url() {
+        let path = record_path(&record(
+            "http://example.com/docs/page.html?lang=en",
+            "2026-10-18T07:15:40Z",
+            "<urn:uuid:1>",
+        ));
+        let name = path.file_name().unwrap().to_str().unwrap();
+        assert_eq!(path.parent().unwrap(), Path::new("example.com/docs"));
+        assert!(name.starts_with("page.html-20261018071540-"));
+        assert!(name.ends_with(".txt"));
+    }
+
+    #[test]
+    fn record_paths_of_long_urls_can_be_written() {
+        let segment = "%D8%B5%D9%81%D8%AD%D8%A9".repeat(20);
+        let url = format!("http://example.com/{segment}/{segment}.html");
+        let path = record_path(&record(&url, "2026-10-18T07:15:40Z", "<urn:uuid:1>"));
+        assert!(path.iter().all(|part| part.len() <= 255));
+        let root = std::env::temp_dir().join(format!("txtcrawl-long-url-{}", std::process::id()));
+        write_new(&root.join(&path), b"text").unwrap();
+        assert_eq!(fs::read(root.join(&path)).unwrap(), b"text");
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn record_paths_differ_per_record() {
+        let date = "2026-10-18T07:15:40Z";
+        let first = record_path(&record("http://example.com/a?page=1", date, "<urn:uuid:1>"));
+        let second = record_path(&record("http://example.com/a?page=2", date, "<urn:uuid:2>"));
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn long_segments_are_capped() {
+        let long = "%D8%A7".repeat(40);
+        let first = sanitize(&long);
+        let second = sanitize(&format!("{long}x"));
+        assert_eq!(first.len(), MAX_SEGMENT_LEN);
+        assert_ne!(first, second);
+        assert_eq!(sanitize("short"), "short");
+    }
+
+    #[test]
+    fn write_new_does_not_overwrite() {
+        let path = std::env::temp_dir().join(format!("txtcrawl-write-new-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        write_new(&path, b"first").unwrap();
+        assert!(write_new(&path, b"second").is_err());
+        assert_eq!(fs::read(&path).unwrap(), b"first");
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,14 +1,122 @@
+use bytes::{Bytes, BytesMut};
+use encoding_rs::Encoding;
+use isahc::http::header::HOST;
 use isahc::http::{HeaderMap, HeaderValue, StatusCode, Version};
-use reqwest::Response as Resp;
+use libflate::{gzip, zlib};
+use reqwest::{Request, Response as Resp};
 use soup::Soup;
+use std::borrow::Cow;
+use std::cell::OnceCell;
+use std::io::Read;
 use std::net::IpAddr;
 use warc::{BufferedBody, RawRecordHeader, Record, RecordType, WarcHeader};
 
-use crate::crawl_utils;
+use crate::document::{DocumentError, DocumentKind};
+use crate::extract::TextExtractor;
+use crate::links::PageLink;
+use crate::metadata::PageMetadata;
+use crate::mime::{MimePolicy, SNIFF_LEN};
+use crate::robots::RobotsDirectives;
+use crate::{charset, crawl_utils, document, mime};
+
+pub const WARC_VERSION: &str = "1.1";
+pub const SOFTWARE: &str = concat!(
+    "txtcrawl/",
+    env!("CARGO_PKG_VERSION"),
+    " (",
+    env!("CARGO_PKG_REPOSITORY"),
+    ")"
+);
+
+/// Comma separated ISO 639-3 codes of the languages identified in a record's text, the language
+/// of most of the text first
+pub const LANGUAGE_HEADER: &str = "warc-identified-content-language";
+
+/// Share of the text in each identified language, as comma separated `code:share` pairs
+pub const LANGUAGE_DISTRIBUTION_HEADER: &str = "warc-identified-language-distribution";
+
+/// Mean confidence of the language identification, between 0 and 1
+pub const LANGUAGE_CONFIDENCE_HEADER: &str = "warc-identified-language-confidence";
+
+/// Charset the text of a conversion record was decoded from
+pub const CHARSET_HEADER: &str = "warc-identified-content-charset";
+
+/// `TextExtractor` or document format that produced the text of a conversion record
+pub const EXTRACTOR_HEADER: &str = "warc-text-extractor";
+
+/// Current time in the ISO 8601 UTC form required by `WARC-Date`
+pub fn warc_date() -> String {
+    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+}
 
 pub struct WetRecord {
     pub headers: RawRecordHeader,
-    pub body: String,
+    pub body: Vec<u8>,
+}
+
+impl WetRecord {
+    pub fn record_id(&self) -> Vec<u8> {
+        self.headers
+            .headers
+            .get(&WarcHeader::RecordID)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn header(&self, header: WarcHeader) -> Option<String> {
+        self.headers
+            .headers
+            .get(&header)
+            .map(|value| String::from_utf8_lossy(value).to_string())
+    }
+
+    /// `warcinfo` record that opens every output file, `fields` are written as `application/warc-fields`
+    pub fn warcinfo(filename: &str, fields: &[(String, String)]) -> Self {
+        let mut body = String::new();
+        for (name, value) in fields {
+            body.push_str(name);
+            body.push_str(": ");
+            body.push_str(value);
+            body.push_str("\r\n");
+        }
+        let headers = RawRecordHeader {
+            version: WARC_VERSION.to_owned(),
+            headers: vec![
+                (
+                    WarcHeader::RecordID,
+                    Record::<BufferedBody>::generate_record_id().into_bytes(),
+                ),
+                (
+                    WarcHeader::WarcType,
+                    RecordType::WarcInfo.to_string().into_bytes(),
+                ),
+                (WarcHeader::Date, warc_date().into_bytes()),
+                (WarcHeader::Filename, filename.as_bytes().to_vec()),
+                (
+                    WarcHeader::ContentType,
+                    b"application/warc-fields".to_vec(),
+                ),
+                (
+                    WarcHeader::ContentLength,
+                    body.len().to_string().into_bytes(),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        };
+        WetRecord {
+            headers,
+            body: body.into_bytes(),
+        }
+    }
+}
+
+/// Request a response was received for, archived as the `request` record
+pub struct SentRequest<'a> {
+    /// the last request sent, the one the response answers when redirects were followed
+    pub request: &'a Request,
+    /// `WARC-Date` of the capture
+    pub time: &'a str,
 }
 
 pub struct Response {
@@ -16,10 +124,17 @@ pub struct Response {
     version: String,
     status: String,
     url: String,
-    data: String,
+    data: Bytes,
     content_length: u64,
-    headers: String,
+    headers: Vec<u8>,
+    content_type: String,
+    header_charset: Option<String>,
+    content_encoding: String,
+    content_language: Option<String>,
+    robots_tags: Vec<String>,
+    request_head: Vec<u8>,
     time: String,
+    decoded: OnceCell<(String, &'static Encoding)>,
 }
 
 impl Response {
@@ -28,12 +143,42 @@ impl Response {
         version: Version,
         status: StatusCode,
         url: &str,
-        data: &str,
-        content_length: u64,
+        data: Bytes,
         headers: &HeaderMap,
-        time: &str,
+        request: SentRequest,
     ) -> Self {
+        let content_length = data.len() as u64;
+        let content_type_header = headers
+            .get("content-type")
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        let content_type = content_type_header
+            .split(';')
+            .next()
+            .map(|mime| mime.trim().to_lowercase())
+            .unwrap_or_default();
+        let header_charset = charset::content_type_charset(content_type_header);
+        let content_encoding = headers
+            .get("content-encoding")
+            .and_then(|value| value.to_str().ok())
+            .map(|encoding| encoding.trim().to_lowercase())
+            .unwrap_or_default();
+        let content_language = headers
+            .get("content-language")
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let robots_tags = headers
+            .get_all("x-robots-tag")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .map(str::to_string)
+            .collect();
+        // the payload is stored de-chunked, so the archived headers state the length received and
+        // keep the original framing under a name replay tools do not act on
         let mut headers = headers.clone();
+        if let Some(encoding) = headers.remove("transfer-encoding") {
+            headers.insert("x-archive-orig-transfer-encoding", encoding);
+        }
         headers.insert("content-length", HeaderValue::from(content_length));
         let headers = crawl_utils::http_headers_fmt(&headers);
         Response {
@@ -41,79 +186,332 @@ impl Response {
             version: format!("{version:?}"),
             status: status.to_string(),
             url: url.to_string(),
-            data: data.to_string(),
+            data,
             content_length,
             headers,
-            time: time.to_string(),
+            content_type,
+            header_charset,
+            content_encoding,
+            content_language,
+            robots_tags,
+            request_head: request_head(request.request),
+            time: request.time.to_string(),
+            decoded: OnceCell::new(),
         }
     }
+    /// Final URL of the response, after redirects
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
     pub fn to_soup(&self) -> Soup {
-        return Soup::new(self.data.as_str());
+        return Soup::new(self.body_text());
     }
 
-    pub fn to_warcrecord(&self, soup: Option<&Soup>) -> WetRecord {
-        let text = match soup {
-            Some(my_soup) => my_soup.text(),
-            None => self.to_soup().text(),
+    /// Payload with its `Content-Encoding` removed, the payload itself is kept as received.
+    /// Falls back to the payload as is when the encoding is unknown or broken
+    pub fn decoded_body(&self) -> Cow<'_, [u8]> {
+        let mut decoded = Vec::new();
+        let decoding = match self.content_encoding.as_str() {
+            "gzip" | "x-gzip" => {
+                gzip::Decoder::new(self.data.as_ref()).and_then(|mut d| d.read_to_end(&mut decoded))
+            }
+            "deflate" => zlib::Decoder::new(self.data.as_ref())
+                .and_then(|mut d| d.read_to_end(&mut decoded)),
+            _ => return Cow::Borrowed(self.data.as_ref()),
         };
-        let datetime = self.time.as_bytes().to_vec();
-        let ip = self.ip.as_bytes().to_vec();
-        let headers = RawRecordHeader {
-            version: "1.0".to_owned(),
+        match decoding {
+            Ok(_) => Cow::Owned(decoded),
+            Err(_) => Cow::Borrowed(self.data.as_ref()),
+        }
+    }
+
+    /// Decoded payload as a string for text extraction, see `charset::decode`
+    pub fn body_text(&self) -> &str {
+        &self.decoded().0
+    }
+
+    /// Charset `body_text` was decoded from
+    pub fn charset(&self) -> &'static str {
+        self.decoded().1.name()
+    }
+
+    fn decoded(&self) -> &(String, &'static Encoding) {
+        self.decoded.get_or_init(|| {
+            charset::decode(
+                &self.decoded_body(),
+                self.header_charset.as_deref(),
+                &self.url,
+            )
+        })
+    }
+
+    pub fn text(&self, soup: Option<&Soup>, extractor: TextExtractor) -> String {
+        match soup {
+            Some(my_soup) => extractor.extract(my_soup),
+            None => extractor.extract(&self.to_soup()),
+        }
+    }
+
+    /// Title, description, dates and other cataloguing fields of the page
+    pub fn metadata(&self, soup: &Soup) -> PageMetadata {
+        PageMetadata::from_soup(soup, &self.url, self.content_language.as_deref())
+    }
+
+    /// Opt-outs of the `X-Robots-Tag` headers and, when `soup` is given, of the robots `<meta>` tags
+    pub fn robots_directives(&self, soup: Option<&Soup>) -> RobotsDirectives {
+        let mut directives = RobotsDirectives::from_headers(&self.robots_tags);
+        if let Some(soup) = soup {
+            directives.merge(RobotsDirectives::from_soup(soup));
+        }
+        directives
+    }
+
+    /// Text conversion of the page, `refers_to` links it back to the `response` record it was
+    /// extracted from when the full capture is archived. The page metadata is added as
+    /// `WARC-Page-*` headers
+    pub fn to_warcrecord(
+        &self,
+        soup: Option<&Soup>,
+        extractor: TextExtractor,
+        refers_to: Option<&[u8]>,
+    ) -> WetRecord {
+        let parsed;
+        let soup = match soup {
+            Some(soup) => soup,
+            None => {
+                parsed = self.to_soup();
+                &parsed
+            }
+        };
+        let text = self.text(Some(soup), extractor);
+        let mut record = self.conversion_record(text, extractor.name(), refers_to);
+        record.headers.headers.insert(
+            WarcHeader::Unknown(CHARSET_HEADER.to_string()),
+            self.charset().as_bytes().to_vec(),
+        );
+        for (name, value) in self.metadata(soup).headers() {
+            record
+                .headers
+                .headers
+                .insert(WarcHeader::Unknown(name.to_string()), value.as_bytes().to_vec());
+        }
+        record
+    }
+
+    /// Format of the payload, see `DocumentKind::detect`
+    pub fn document_kind(&self) -> DocumentKind {
+        DocumentKind::detect(&self.content_type, &self.url, &self.decoded_body())
+    }
+
+    /// Text of a PDF, DOCX or ODT payload
+    pub fn document_text(&self, kind: DocumentKind) -> Result<String, DocumentError> {
+        document::document_text(kind, &self.decoded_body())
+    }
+
+    /// Absolute targets of the links of a PDF payload
+    pub fn pdf_links(&self) -> Vec<String> {
+        document::pdf_links(&self.decoded_body(), &self.url)
+    }
+
+    /// Text conversion of a document that is not HTML, `text` comes from `document_text`
+    pub fn to_document_record(
+        &self,
+        kind: DocumentKind,
+        text: String,
+        refers_to: Option<&[u8]>,
+    ) -> WetRecord {
+        self.conversion_record(text, kind.name(), refers_to)
+    }
+
+    fn conversion_record(
+        &self,
+        text: String,
+        extractor: &str,
+        refers_to: Option<&[u8]>,
+    ) -> WetRecord {
+        let mut headers = self.record_header(RecordType::Conversion, "text/plain", text.len());
+        headers
+            .headers
+            .insert(WarcHeader::IdentifiedPayloadType, b"text/plain".to_vec());
+        if let Some(id) = refers_to {
+            headers.headers.insert(WarcHeader::RefersTo, id.to_vec());
+        }
+        headers.headers.insert(
+            WarcHeader::Unknown(EXTRACTOR_HEADER.to_string()),
+            extractor.as_bytes().to_vec(),
+        );
+        WetRecord {
+            headers,
+            body: text.into_bytes(),
+        }
+    }
+
+    /// `response` record holding the status line, headers and payload as received
+    pub fn to_response_record(&self) -> WetRecord {
+        let mut body = format!("{} {}\r\n", self.version, self.status).into_bytes();
+        body.extend_from_slice(&self.headers);
+        body.extend_from_slice(b"\r\n\r\n");
+        body.extend_from_slice(&self.data);
+        let mut headers = self.record_header(
+            RecordType::Response,
+            "application/http; msgtype=response",
+            body.len(),
+        );
+        if !self.content_type.is_empty() {
+            headers.headers.insert(
+                WarcHeader::IdentifiedPayloadType,
+                self.content_type.clone().into_bytes(),
+            );
+        }
+        WetRecord { headers, body }
+    }
+
+    /// `request` record holding the request line and headers sent for `response_id`
+    pub fn to_request_record(&self, response_id: &[u8]) -> WetRecord {
+        let mut body = self.request_head.clone();
+        body.extend_from_slice(b"\r\n\r\n");
+        let mut headers = self.record_header(
+            RecordType::Request,
+            "application/http; msgtype=request",
+            body.len(),
+        );
+        headers
+            .headers
+            .insert(WarcHeader::ConcurrentTo, response_id.to_vec());
+        WetRecord { headers, body }
+    }
+
+    /// `metadata` record listing the outlinks of the page, one `outlink: <url> <source>` field per
+    /// link, `concurrent_to` is the `response` record of the capture or the `conversion` record
+    /// when only the text is archived
+    pub fn to_metadata_record(&self, concurrent_to: &[u8], links: &[PageLink]) -> WetRecord {
+        let body = links
+            .iter()
+            .map(|link| format!("outlink: {} {}\r\n", link.url, link.source.name()))
+            .collect::<String>()
+            .into_bytes();
+        let mut headers =
+            self.record_header(RecordType::Metadata, "application/warc-fields", body.len());
+        headers
+            .headers
+            .insert(WarcHeader::ConcurrentTo, concurrent_to.to_vec());
+        WetRecord { headers, body }
+    }
+
+    fn record_header(
+        &self,
+        record_type: RecordType,
+        content_type: &str,
+        content_length: usize,
+    ) -> RawRecordHeader {
+        RawRecordHeader {
+            version: WARC_VERSION.to_owned(),
             headers: vec![
                 (
                     WarcHeader::RecordID,
                     Record::<BufferedBody>::generate_record_id().into_bytes(),
                 ),
                 (WarcHeader::TargetURI, self.url.clone().into_bytes()),
-                (
-                    WarcHeader::WarcType,
-                    RecordType::WarcInfo.to_string().into_bytes(),
-                ),
-                (WarcHeader::Date, datetime),
-                (WarcHeader::IPAddress, ip),
+                (WarcHeader::WarcType, record_type.to_string().into_bytes()),
+                (WarcHeader::Date, self.time.as_bytes().to_vec()),
+                (WarcHeader::IPAddress, self.ip.as_bytes().to_vec()),
+                (WarcHeader::ContentType, content_type.as_bytes().to_vec()),
                 (
                     WarcHeader::ContentLength,
-                    text.len().to_string().into_bytes(),
+                    content_length.to_string().into_bytes(),
                 ),
             ]
             .into_iter()
             .collect(),
-        };
-        WetRecord {
-            headers,
-            body: text,
         }
     }
-    pub async fn from_request(resp: Resp) -> Result<Self, ResponseError> {
-        let content_length: u64 = resp.content_length().unwrap_or(0);
+    /// Downloads the payload of `resp`, it is abandoned with `ResponseError::Skipped` as soon as its
+    /// `Content-Type`, or the type sniffed from its first bytes when there is none, is rejected by
+    /// `mime_policy`
+    pub async fn from_request(
+        mut resp: Resp,
+        request: &Request,
+        mime_policy: &MimePolicy,
+    ) -> Result<Self, ResponseError> {
 
         let headers = resp.headers().clone();
+        let content_type = headers
+            .get("content-type")
+            .and_then(|value| value.to_str().ok());
+        if let Some(content_type) = content_type {
+            mime_policy
+                .check(content_type)
+                .map_err(ResponseError::Skipped)?;
+        }
+        // compressed payloads cannot be sniffed before they are decoded
+        let mut sniffed = content_type.is_some() || headers.contains_key("content-encoding");
         let ip = resp.remote_addr().unwrap().ip();
         let version = resp.version();
         let status = resp.status();
         let url = resp.url().clone();
-        let text = resp.text().await;
-        if text.is_err() {
-            return Err(ResponseError::TextError);
+        let mut data = BytesMut::new();
+        loop {
+            match resp.chunk().await {
+                Ok(Some(chunk)) => data.extend_from_slice(&chunk),
+                Ok(None) => break,
+                Err(_) => return Err(ResponseError::BodyError),
+            }
+            if !sniffed && data.len() >= SNIFF_LEN {
+                sniffed = true;
+                mime_policy
+                    .check(mime::sniff(&data[..SNIFF_LEN]))
+                    .map_err(ResponseError::Skipped)?;
+            }
+        }
+        if !sniffed {
+            mime_policy
+                .check(mime::sniff(&data))
+                .map_err(ResponseError::Skipped)?;
         }
-        let text = text.unwrap();
-        let time = chrono::Local::now().to_string();
+        let data = data.freeze();
+        let time = warc_date();
 
         let response = Response::new(
             ip,
             version,
             status,
             url.as_str(),
-            text.as_str(),
-            content_length,
+            data,
             &headers,
-            time.as_str(),
+            SentRequest {
+                request,
+                time: time.as_str(),
+            },
         );
         Ok(response)
     }
 }
 
+/// Request line and headers of `request`, without the blank line ending them. `Host` is added by
+/// hyper when the request is sent, so it is derived from the URL here the same way
+fn request_head(request: &Request) -> Vec<u8> {
+    let url = request.url();
+    let target = match url.query() {
+        Some(query) => format!("{}?{query}", url.path()),
+        None => url.path().to_string(),
+    };
+    let mut head = format!("{} {target} {:?}", request.method(), request.version()).into_bytes();
+    if !request.headers().contains_key(HOST) {
+        let host = url.host_str().unwrap_or_default();
+        let host = match url.port() {
+            Some(port) => format!("{host}:{port}"),
+            None => host.to_string(),
+        };
+        head.extend_from_slice(format!("\r\nhost: {host}").as_bytes());
+    }
+    if !request.headers().is_empty() {
+        head.extend_from_slice(b"\r\n");
+        head.extend_from_slice(&crawl_utils::http_headers_fmt(request.headers()));
+    }
+    head
+}
+
 // impl From<Resp> for Response {
 //     async fn from(resp: Resp) -> Result<Self,Err(ResponseError)> {
 //
@@ -124,5 +522,7 @@ impl Response {
 #[derive(Debug)]
 pub enum ResponseError {
     RequestError,
-    TextError,
+    BodyError,
+    /// the content type is not wanted, holds the reason
+    Skipped(String),
 }
@@ -1,8 +1,12 @@
 use chashmap_async::CHashMap;
+use config::Config;
+use soup::{NodeExt, QueryBuilderExt, Soup};
 use texting_robots::{get_robots_url, Robot};
 use tokio::time::Instant;
 use url::{ParseError, Url};
 
+use crate::links::PageLink;
+
 pub struct Robots {
     permissions: CHashMap<Url, Rules>,
 }
@@ -88,3 +92,186 @@ impl RobotsVerdict {
         }
     }
 }
+
+/// Product token matched against crawler-specific `<meta name>` and `X-Robots-Tag` directives
+pub const ROBOTS_AGENT: &str = "txtcrawl";
+
+/// Page-level opt-outs of a response, from its `X-Robots-Tag` headers and robots `<meta>` tags
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RobotsDirectives {
+    pub noindex: bool,
+    pub nofollow: bool,
+    pub noarchive: bool,
+}
+
+impl RobotsDirectives {
+    /// Directives of `X-Robots-Tag` values, those prefixed with the name of another crawler such
+    /// as `googlebot: noindex` are ignored
+    pub fn from_headers(values: &[String]) -> Self {
+        let mut directives = Self::default();
+        for value in values {
+            match value.split_once(':') {
+                Some((agent, rules)) if !agent.contains(',') => {
+                    if agent.trim().eq_ignore_ascii_case(ROBOTS_AGENT) {
+                        directives.add(rules);
+                    }
+                }
+                _ => directives.add(value),
+            }
+        }
+        directives
+    }
+
+    /// Directives of the `<meta name="robots">` tags and of those named after `ROBOTS_AGENT`
+    pub fn from_soup(soup: &Soup) -> Self {
+        let mut directives = Self::default();
+        for meta in soup.tag("meta").find_all() {
+            let applies = meta.get("name").is_some_and(|name| {
+                let name = name.trim();
+                name.eq_ignore_ascii_case("robots") || name.eq_ignore_ascii_case(ROBOTS_AGENT)
+            });
+            if applies && let Some(content) = meta.get("content") {
+                directives.add(&content);
+            }
+        }
+        directives
+    }
+
+    pub fn merge(&mut self, other: Self) {
+        self.noindex |= other.noindex;
+        self.nofollow |= other.nofollow;
+        self.noarchive |= other.noarchive;
+    }
+
+    /// Adds a comma separated list of directives, unknown ones are ignored
+    fn add(&mut self, rules: &str) {
+        for rule in rules.split([',', ' ']).map(str::trim) {
+            match rule.to_lowercase().as_str() {
+                "noindex" => self.noindex = true,
+                "nofollow" => self.nofollow = true,
+                "noarchive" => self.noarchive = true,
+                "none" => {
+                    self.noindex = true;
+                    self.nofollow = true;
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Which page-level opt-outs the crawl honours, each one is switched by its own job field
+#[derive(Clone, Copy, Debug)]
+pub struct RobotsPolicy {
+    /// `respect_noindex` : nothing is archived of `noindex` pages
+    pub noindex: bool,
+    /// `respect_noarchive` : nothing is archived of `noarchive` pages
+    pub noarchive: bool,
+    /// `respect_nofollow` : the links of `nofollow` pages are neither extracted nor followed
+    pub nofollow: bool,
+    /// `respect_link_nofollow` : links with `rel="nofollow"` are not followed
+    pub link_nofollow: bool,
+}
+
+impl RobotsPolicy {
+    pub fn from_job(job: &Config) -> Self {
+        Self {
+            noindex: job.get_bool("respect_noindex").unwrap(),
+            noarchive: job.get_bool("respect_noarchive").unwrap(),
+            nofollow: job.get_bool("respect_nofollow").unwrap(),
+            link_nofollow: job.get_bool("respect_link_nofollow").unwrap(),
+        }
+    }
+
+    /// Whether records of a page with `directives` may be written
+    pub fn archives(&self, directives: &RobotsDirectives) -> bool {
+        let opted_out =
+            (self.noindex && directives.noindex) || (self.noarchive && directives.noarchive);
+        !opted_out
+    }
+
+    /// Whether links of a page with `directives` may be extracted
+    pub fn follows(&self, directives: &RobotsDirectives) -> bool {
+        !(self.nofollow && directives.nofollow)
+    }
+
+    /// Whether `link` may be followed, it is left out when it has `rel="nofollow"`
+    pub fn follows_link(&self, link: &PageLink) -> bool {
+        !(self.link_nofollow && link.nofollow)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::links::{LinkSource, page_links};
+
+    const RESPECT_ALL: RobotsPolicy = RobotsPolicy {
+        noindex: true,
+        noarchive: true,
+        nofollow: true,
+        link_nofollow: true,
+    };
+
+    fn meta(name: &str, content: &str) -> RobotsDirectives {
+        let html = format!("<html><head><meta name='{name}' content='{content}'></head></html>");
+        RobotsDirectives::from_soup(&Soup::new(&html))
+    }
+
+    #[test]
+    fn meta_content_is_case_insensitive() {
+        let directives = meta("robots", "noindex, NOFOLLOW");
+        assert!(directives.noindex && directives.nofollow && !directives.noarchive);
+    }
+
+    #[test]
+    fn meta_of_other_crawlers_is_ignored() {
+        assert!(meta("txtcrawl", "noarchive").noarchive);
+        assert_eq!(
+            meta("googlebot", "noindex, nofollow"),
+            RobotsDirectives::default()
+        );
+    }
+
+    #[test]
+    fn header_directives_with_and_without_agent() {
+        let plain = RobotsDirectives::from_headers(&["noindex, nofollow".to_string()]);
+        assert!(plain.noindex && plain.nofollow);
+        let ours = RobotsDirectives::from_headers(&["txtcrawl: noarchive".to_string()]);
+        assert!(ours.noarchive && !ours.noindex);
+        let other = RobotsDirectives::from_headers(&["googlebot: noindex".to_string()]);
+        assert_eq!(other, RobotsDirectives::default());
+    }
+
+    #[test]
+    fn nofollow_links_are_dropped() {
+        let soup =
+            Soup::new("<a href='/kept'>a</a><a href='/dropped' rel='external nofollow'>b</a>");
+        let followed: Vec<String> = page_links(&soup, "http://example.com/", LinkSource::ALL)
+            .into_iter()
+            .filter(|link| RESPECT_ALL.follows_link(link))
+            .map(|link| link.url)
+            .collect();
+        assert_eq!(followed, ["http://example.com/kept"]);
+    }
+
+    #[test]
+    fn noindex_and_noarchive_block_the_write() {
+        let noindex = RobotsDirectives {
+            noindex: true,
+            ..Default::default()
+        };
+        let noarchive = RobotsDirectives {
+            noarchive: true,
+            ..Default::default()
+        };
+        assert!(!RESPECT_ALL.archives(&noindex));
+        assert!(!RESPECT_ALL.archives(&noarchive));
+        assert!(RESPECT_ALL.archives(&RobotsDirectives::default()));
+        let ignore_noindex = RobotsPolicy {
+            noindex: false,
+            ..RESPECT_ALL
+        };
+        assert!(ignore_noindex.archives(&noindex));
+    }
+}
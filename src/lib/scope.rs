@@ -0,0 +1,299 @@
+use std::net::Ipv4Addr;
+
+use ahash::AHashSet;
+use config::Config;
+use regex::Regex;
+use url::{Host, Url};
+
+use crate::CrawlEntry;
+
+/// Schemes the crawler can fetch, the only valid `scope_protocols` entries
+pub const PROTOCOLS: &[&str] = &["http", "https"];
+
+/// How far from the seeds a crawl may go
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScopeMode {
+    /// any host
+    Any,
+    /// the host of one of the seeds, and its subdomains with `scope_subdomains`
+    Host,
+    /// the registered domain of one of the seeds, `example.co.uk` for `www.example.co.uk`
+    Domain,
+}
+
+impl ScopeMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "any" => Some(Self::Any),
+            "host" => Some(Self::Host),
+            "domain" => Some(Self::Domain),
+            _ => None,
+        }
+    }
+}
+
+/// Rule a discovered link was left out by, in the order the rules are checked
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScopeRejection {
+    /// the URL has no host or its scheme is not among `scope_protocols`
+    Protocol,
+    /// the host is not a seed host with `scope_mode = "host"`
+    OffHost,
+    /// the registered domain is not one of the seeds with `scope_mode = "domain"`
+    OffDomain,
+    /// the host is under one of `scope_deny_domains`
+    DeniedDomain,
+    /// the host is under none of `scope_allow_domains`
+    UnlistedDomain,
+    /// the URL matches one of `scope_exclude`
+    Excluded,
+    /// the URL matches none of `scope_include`
+    NotIncluded,
+}
+
+impl ScopeRejection {
+    pub const ALL: [ScopeRejection; 7] = [
+        Self::Protocol,
+        Self::OffHost,
+        Self::OffDomain,
+        Self::DeniedDomain,
+        Self::UnlistedDomain,
+        Self::Excluded,
+        Self::NotIncluded,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Protocol => "protocol",
+            Self::OffHost => "off host",
+            Self::OffDomain => "off domain",
+            Self::DeniedDomain => "denied domain",
+            Self::UnlistedDomain => "unlisted domain",
+            Self::Excluded => "excluded",
+            Self::NotIncluded => "not included",
+        }
+    }
+}
+
+/// Where the links found during a crawl may lead, seeds are always crawled
+#[derive(Clone, Debug)]
+pub struct CrawlScope {
+    mode: ScopeMode,
+    subdomains: bool,
+    seed_hosts: AHashSet<String>,
+    seed_domains: AHashSet<String>,
+    allow_domains: Vec<String>,
+    deny_domains: Vec<String>,
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
+    protocols: Vec<String>,
+}
+
+impl CrawlScope {
+    /// Scope of the `scope_*` job fields around the hosts of `seeds`
+    pub fn from_job(job: &Config, seeds: &[CrawlEntry]) -> Self {
+        let strings = |key: &str| -> Vec<String> {
+            job.get_array(key)
+                .unwrap()
+                .into_iter()
+                .map(|value| value.into_string().unwrap())
+                .collect()
+        };
+        let regexes = |key: &str| -> Vec<Regex> {
+            strings(key)
+                .iter()
+                .map(|pattern| Regex::new(pattern).unwrap())
+                .collect()
+        };
+        let domains = |key: &str| -> Vec<String> {
+            strings(key)
+                .into_iter()
+                .map(|domain| domain.trim().trim_matches('.').to_lowercase())
+                .filter(|domain| !domain.is_empty())
+                .collect()
+        };
+        let mut protocols: Vec<String> = strings("scope_protocols")
+            .iter()
+            .map(|protocol| normalize_protocol(protocol))
+            .collect();
+        if protocols.is_empty() {
+            protocols = PROTOCOLS.iter().map(|protocol| protocol.to_string()).collect();
+        }
+        let seed_hosts: AHashSet<String> = seeds
+            .iter()
+            .filter_map(|seed| Url::parse(&seed.url).ok().map(|url| host_of(&url)))
+            .collect();
+        Self {
+            mode: ScopeMode::from_name(&job.get_string("scope_mode").unwrap()).unwrap(),
+            subdomains: job.get_bool("scope_subdomains").unwrap(),
+            seed_domains: seed_hosts
+                .iter()
+                .map(|host| registered_domain(host).to_string())
+                .collect(),
+            seed_hosts,
+            allow_domains: domains("scope_allow_domains"),
+            deny_domains: domains("scope_deny_domains"),
+            include: regexes("scope_include"),
+            exclude: regexes("scope_exclude"),
+            protocols,
+        }
+    }
+
+    /// `Err` holds the first rule `url` breaks
+    pub fn check(&self, url: &str) -> Result<(), ScopeRejection> {
+        let Ok(parsed) = Url::parse(url) else {
+            return Err(ScopeRejection::Protocol);
+        };
+        if parsed.cannot_be_a_base()
+            || parsed.host_str().is_none_or(str::is_empty)
+            || !self.protocols.iter().any(|p| p == parsed.scheme())
+        {
+            return Err(ScopeRejection::Protocol);
+        }
+        let host = host_of(&parsed);
+        match self.mode {
+            ScopeMode::Any => {}
+            ScopeMode::Host => {
+                let on_host = self.seed_hosts.contains(&host)
+                    || (self.subdomains
+                        && self.seed_hosts.iter().any(|seed| is_subdomain(&host, seed)));
+                if !on_host {
+                    return Err(ScopeRejection::OffHost);
+                }
+            }
+            ScopeMode::Domain => {
+                if !self.seed_domains.contains(registered_domain(&host)) {
+                    return Err(ScopeRejection::OffDomain);
+                }
+            }
+        }
+        let under = |domain: &String| host == *domain || is_subdomain(&host, domain);
+        if self.deny_domains.iter().any(under) {
+            return Err(ScopeRejection::DeniedDomain);
+        }
+        if !self.allow_domains.is_empty() && !self.allow_domains.iter().any(under) {
+            return Err(ScopeRejection::UnlistedDomain);
+        }
+        if self.exclude.iter().any(|pattern| pattern.is_match(url)) {
+            return Err(ScopeRejection::Excluded);
+        }
+        if !self.include.is_empty() && !self.include.iter().any(|pattern| pattern.is_match(url)) {
+            return Err(ScopeRejection::NotIncluded);
+        }
+        Ok(())
+    }
+}
+
+/// Whether `host` is a strict subdomain of `domain`
+fn is_subdomain(host: &str, domain: &str) -> bool {
+    host.strip_suffix(domain)
+        .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+/// Lowercase host of `url` without a trailing dot, IPv6 addresses in brackets
+fn host_of(url: &Url) -> String {
+    match url.host() {
+        Some(Host::Domain(domain)) => domain.trim_end_matches('.').to_string(),
+        Some(host) => host.to_string(),
+        None => String::new(),
+    }
+}
+
+/// Registered domain of `host` according to the public suffix list, the host itself for IP
+/// addresses and hosts without one
+fn registered_domain(host: &str) -> &str {
+    if host.starts_with('[') || host.parse::<Ipv4Addr>().is_ok() {
+        return host;
+    }
+    psl::domain_str(host).unwrap_or(host)
+}
+
+/// `scope_protocols` entry as a scheme, `HTTPS:` is `https`
+fn normalize_protocol(protocol: &str) -> String {
+    protocol.trim().trim_end_matches(':').to_lowercase()
+}
+
+/// Whether `protocol` is a valid `scope_protocols` entry
+pub fn valid_protocol(protocol: &str) -> bool {
+    PROTOCOLS.contains(&normalize_protocol(protocol).as_str())
+}
+
+/// Whether `pattern` is a valid `scope_include` or `scope_exclude` entry
+pub fn valid_pattern(pattern: &str) -> bool {
+    Regex::new(pattern).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scope(mode: ScopeMode, protocols: &[&str]) -> CrawlScope {
+        let seed_hosts: AHashSet<String> = ["www.example.co.uk".to_string()].into();
+        CrawlScope {
+            mode,
+            subdomains: false,
+            seed_domains: ["example.co.uk".to_string()].into(),
+            seed_hosts,
+            allow_domains: Vec::new(),
+            deny_domains: Vec::new(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            protocols: protocols
+                .iter()
+                .map(|protocol| protocol.to_string())
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn urls_without_host_are_rejected() {
+        let scope = scope(ScopeMode::Any, &["http", "https", "file"]);
+        assert_eq!(
+            scope.check("file:///etc/passwd"),
+            Err(ScopeRejection::Protocol)
+        );
+        assert_eq!(
+            scope.check("mailto:someone@example.com"),
+            Err(ScopeRejection::Protocol)
+        );
+        assert_eq!(scope.check("not a url"), Err(ScopeRejection::Protocol));
+    }
+
+    #[test]
+    fn schemes_outside_protocols_are_rejected() {
+        let scope = scope(ScopeMode::Any, &["https"]);
+        assert_eq!(
+            scope.check("http://example.com/"),
+            Err(ScopeRejection::Protocol)
+        );
+        assert_eq!(
+            scope.check("ftp://example.com/file"),
+            Err(ScopeRejection::Protocol)
+        );
+        assert_eq!(scope.check("https://example.com/"), Ok(()));
+    }
+
+    #[test]
+    fn hosts_and_domains() {
+        let host = scope(ScopeMode::Host, PROTOCOLS);
+        assert_eq!(host.check("http://www.example.co.uk/a"), Ok(()));
+        assert_eq!(
+            host.check("http://news.example.co.uk/"),
+            Err(ScopeRejection::OffHost)
+        );
+        let domain = scope(ScopeMode::Domain, PROTOCOLS);
+        assert_eq!(domain.check("http://news.example.co.uk/"), Ok(()));
+        assert_eq!(
+            domain.check("http://other.co.uk/"),
+            Err(ScopeRejection::OffDomain)
+        );
+    }
+
+    #[test]
+    fn only_fetchable_protocols_are_valid() {
+        assert!(valid_protocol("HTTPS:"));
+        assert!(valid_protocol(" http "));
+        assert!(!valid_protocol("file"));
+        assert!(!valid_protocol("ftp"));
+    }
+}
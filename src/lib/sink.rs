@@ -0,0 +1,180 @@
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+use config::Config;
+use serde_json::json;
+use warc::{RecordType, WarcHeader};
+
+use crate::canonical::Canonicalizer;
+use crate::digest::DigestAlgorithm;
+use crate::index::IndexFormat;
+use crate::job_config;
+use crate::metadata::{DESCRIPTION_HEADER, PUBLISHED_HEADER, TITLE_HEADER};
+use crate::reader::{record_path, write_new};
+use crate::response::{WetRecord, LANGUAGE_HEADER, SOFTWARE, WARC_VERSION};
+use crate::writer::{output_prefix, FileNameTemplate, RotatingWriter, RotationPolicy};
+
+/// Destination of the records produced by a crawl, the writer thread hands every sink the
+/// records of each fetch. Implement it to send pages somewhere `txtcrawl` does not know about
+pub trait RecordSink: Send {
+    /// Writes the records of one fetch, digests have already been added to their headers
+    fn write_batch(&mut self, batch: &[WetRecord]) -> io::Result<()>;
+
+    /// Called once after the last batch
+    fn finish(&mut self) -> io::Result<()>;
+}
+
+impl RecordSink for RotatingWriter {
+    fn write_batch(&mut self, batch: &[WetRecord]) -> io::Result<()> {
+        RotatingWriter::write_batch(self, batch)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.rotate()
+    }
+}
+
+/// One JSON object per page with its url, timestamp, language, title, description, publication
+/// date and text
+pub struct JsonlSink {
+    file: BufWriter<File>,
+}
+
+impl JsonlSink {
+    pub fn create(path: &str) -> io::Result<Self> {
+        Ok(Self {
+            file: BufWriter::new(File::create(path)?),
+        })
+    }
+}
+
+impl RecordSink for JsonlSink {
+    fn write_batch(&mut self, batch: &[WetRecord]) -> io::Result<()> {
+        for record in batch.iter().filter(|record| is_conversion(record)) {
+            let line = json!({
+                "url": record.header(WarcHeader::TargetURI),
+                "timestamp": record.header(WarcHeader::Date),
+                "language": record.header(WarcHeader::Unknown(LANGUAGE_HEADER.to_string())),
+                "title": record.header(WarcHeader::Unknown(TITLE_HEADER.to_string())),
+                "description": record.header(WarcHeader::Unknown(DESCRIPTION_HEADER.to_string())),
+                "published": record.header(WarcHeader::Unknown(PUBLISHED_HEADER.to_string())),
+                "text": String::from_utf8_lossy(&record.body),
+            });
+            writeln!(self.file, "{line}")?;
+        }
+        self.file.flush()
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Plain text corpus, one file per page under `<root>/<host>/<path>/`
+pub struct TextDirSink {
+    root: PathBuf,
+}
+
+impl TextDirSink {
+    pub fn create(root: &str) -> io::Result<Self> {
+        fs::create_dir_all(root)?;
+        Ok(Self {
+            root: PathBuf::from(root),
+        })
+    }
+}
+
+impl RecordSink for TextDirSink {
+    fn write_batch(&mut self, batch: &[WetRecord]) -> io::Result<()> {
+        for record in batch.iter().filter(|record| is_conversion(record)) {
+            write_new(&self.root.join(record_path(record)), &record.body)?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Names accepted in the `sinks` job field
+pub fn valid_sink(name: &str) -> bool {
+    matches!(name, "warc" | "jsonl" | "text")
+}
+
+/// Sinks selected by the `sinks` job field, `warc` writes gzip WARC files next to
+/// `destination_warc`, `jsonl` writes `<prefix>.jsonl` and `text` writes under `<prefix>-text/`
+pub fn job_sinks(job: &Config) -> io::Result<Vec<Box<dyn RecordSink>>> {
+    sinks_at(job, &job.get_string("destination_warc").unwrap())
+}
+
+/// Sinks of the `separate` mode of `archive_languages`, the same as `job_sinks` with
+/// `<prefix>-rejects` as prefix
+pub fn reject_sinks(job: &Config) -> io::Result<Vec<Box<dyn RecordSink>>> {
+    let destination = job.get_string("destination_warc").unwrap();
+    sinks_at(job, &format!("{}-rejects", output_prefix(&destination)))
+}
+
+fn sinks_at(job: &Config, destination: &str) -> io::Result<Vec<Box<dyn RecordSink>>> {
+    let prefix = output_prefix(destination);
+    let mut sinks: Vec<Box<dyn RecordSink>> = Vec::new();
+    for name in job.get_array("sinks").unwrap() {
+        match name.into_string().unwrap().as_str() {
+            "warc" => sinks.push(Box::new(warc_sink(job, destination))),
+            "jsonl" => sinks.push(Box::new(JsonlSink::create(&format!("{prefix}.jsonl"))?)),
+            "text" => sinks.push(Box::new(TextDirSink::create(&format!("{prefix}-text"))?)),
+            _ => {}
+        }
+    }
+    Ok(sinks)
+}
+
+fn warc_sink(job: &Config, destination: &str) -> RotatingWriter {
+    RotatingWriter::new(
+        FileNameTemplate::new(&job.get_string("warc_name_template").unwrap(), destination),
+        RotationPolicy::new(
+            job.get_int("warc_max_size").unwrap() as u64,
+            job.get_int("warc_max_records").unwrap() as u64,
+            job.get_int("warc_max_age").unwrap() as u64,
+        ),
+        warcinfo_fields(job),
+        DigestAlgorithm::from_name(&job.get_string("warc_digest").unwrap()).unwrap(),
+        job.get_array("warc_index")
+            .unwrap()
+            .into_iter()
+            .map(|value| IndexFormat::from_name(&value.into_string().unwrap()).unwrap())
+            .collect(),
+        Canonicalizer::from_job(job),
+    )
+}
+
+/// Contents of the `warcinfo` record: who produced the file and with which job
+fn warcinfo_fields(job: &Config) -> Vec<(String, String)> {
+    let mut fields = vec![
+        ("software".to_string(), SOFTWARE.to_string()),
+        ("format".to_string(), format!("WARC File Format {WARC_VERSION}")),
+        (
+            "conformsTo".to_string(),
+            format!("https://iipc.github.io/warc-specifications/specifications/warc-format/warc-{WARC_VERSION}/"),
+        ),
+    ];
+    if let Ok(operator) = job.get_string("operator") {
+        fields.push(("operator".to_string(), operator));
+    }
+    fields.extend(
+        job_config::job_fields(job)
+            .into_iter()
+            .filter(|(name, _)| name != "operator")
+            .map(|(name, value)| (format!("job-{name}"), value)),
+    );
+    fields
+}
+
+fn is_conversion(record: &WetRecord) -> bool {
+    record
+        .header(WarcHeader::WarcType)
+        .is_some_and(|record_type| RecordType::from(record_type) == RecordType::Conversion)
+}
@@ -0,0 +1,203 @@
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use libflate::gzip::Encoder;
+use warc::WarcWriter;
+
+use crate::canonical::Canonicalizer;
+use crate::digest::{add_digests, DigestAlgorithm};
+use crate::index::{write_indexes, IndexEntry, IndexFormat};
+use crate::response::WetRecord;
+
+pub const DEFAULT_NAME_TEMPLATE: &str = "{prefix}-{timestamp}-{serial}-{host}";
+const EXTENSION: &str = ".warc.gz";
+
+/// Limits after which the current output file is closed and the next one is opened,
+/// `None` means the limit is not checked
+#[derive(Clone, Copy, Default)]
+pub struct RotationPolicy {
+    pub max_size: Option<u64>,
+    pub max_records: Option<u64>,
+    pub max_age: Option<Duration>,
+}
+
+impl RotationPolicy {
+    /// Builds the policy from job values where 0 disables a limit
+    pub fn new(max_size: u64, max_records: u64, max_age_secs: u64) -> Self {
+        Self {
+            max_size: (max_size != 0).then_some(max_size),
+            max_records: (max_records != 0).then_some(max_records),
+            max_age: (max_age_secs != 0).then(|| Duration::from_secs(max_age_secs)),
+        }
+    }
+
+    fn exceeded(&self, size: u64, records: u64, age: Duration) -> bool {
+        self.max_size.is_some_and(|max| size >= max)
+            || self.max_records.is_some_and(|max| records >= max)
+            || self.max_age.is_some_and(|max| age >= max)
+    }
+}
+
+/// Output file names built from a template with the `{prefix}`, `{timestamp}`, `{serial}`
+/// and `{host}` placeholders, the `.warc.gz` extension is always appended
+pub struct FileNameTemplate {
+    template: String,
+    prefix: String,
+    host: String,
+}
+
+impl FileNameTemplate {
+    pub fn new(template: &str, prefix: &str) -> Self {
+        Self {
+            template: template.to_string(),
+            prefix: output_prefix(prefix).to_string(),
+            host: gethostname::gethostname().to_string_lossy().to_string(),
+        }
+    }
+
+    pub fn render(&self, serial: u32) -> String {
+        let timestamp = chrono::Utc::now().format("%Y%m%d%H%M%S").to_string();
+        let mut name = self
+            .template
+            .replace("{prefix}", &self.prefix)
+            .replace("{timestamp}", &timestamp)
+            .replace("{serial}", &format!("{serial:05}"))
+            .replace("{host}", &self.host);
+        name.push_str(EXTENSION);
+        name
+    }
+}
+
+/// `destination_warc` without a `.warc.gz` extension, the base name of every output of a job
+pub fn output_prefix(destination: &str) -> &str {
+    destination.strip_suffix(EXTENSION).unwrap_or(destination)
+}
+
+struct OpenFile {
+    path: String,
+    file: BufWriter<File>,
+    index: Vec<IndexEntry>,
+    offset: u64,
+    records: u64,
+    opened: Instant,
+}
+
+impl OpenFile {
+    /// Compresses `record` as a gzip member of its own, so readers can seek to its offset and
+    /// every record written before a crash stays readable. Returns the offset and compressed length
+    fn write_record(
+        &mut self,
+        record: &WetRecord,
+        canonicalizer: &Canonicalizer,
+    ) -> io::Result<(u64, u64)> {
+        let entry = IndexEntry::from_record(record, canonicalizer);
+        let mut member = Encoder::new(Vec::new())?;
+        WarcWriter::new(&mut member).write_raw(record.headers.clone(), &record.body)?;
+        let member = member.finish().into_result()?;
+        self.file.write_all(&member)?;
+        let offset = self.offset;
+        self.offset += member.len() as u64;
+        self.records += 1;
+        if let Some(mut entry) = entry {
+            entry.locate(&file_name(&self.path), offset, member.len() as u64);
+            self.index.push(entry);
+        }
+        Ok((offset, member.len() as u64))
+    }
+
+    fn finish(mut self, index_formats: &[IndexFormat]) -> io::Result<()> {
+        self.file.flush()?;
+        write_indexes(&self.path, &self.index, index_formats)
+    }
+}
+
+/// Gzip WARC output split over several files according to a `RotationPolicy`,
+/// every file starts with its own `warcinfo` record and holds one gzip member per record.
+/// The indexes of a file are written when it is closed
+pub struct RotatingWriter {
+    names: FileNameTemplate,
+    policy: RotationPolicy,
+    warcinfo_fields: Vec<(String, String)>,
+    digest_algorithm: DigestAlgorithm,
+    index_formats: Vec<IndexFormat>,
+    canonicalizer: Canonicalizer,
+    serial: u32,
+    current: Option<OpenFile>,
+}
+
+impl RotatingWriter {
+    pub fn new(
+        names: FileNameTemplate,
+        policy: RotationPolicy,
+        warcinfo_fields: Vec<(String, String)>,
+        digest_algorithm: DigestAlgorithm,
+        index_formats: Vec<IndexFormat>,
+        canonicalizer: Canonicalizer,
+    ) -> Self {
+        Self {
+            names,
+            policy,
+            warcinfo_fields,
+            digest_algorithm,
+            index_formats,
+            canonicalizer,
+            serial: 0,
+            current: None,
+        }
+    }
+
+    /// Writes the records of one fetch, they always end up in the same file
+    pub fn write_batch(&mut self, batch: &[WetRecord]) -> io::Result<()> {
+        if self.current.as_ref().is_some_and(|file| {
+            self.policy
+                .exceeded(file.offset, file.records, file.opened.elapsed())
+        }) {
+            self.rotate()?;
+        }
+        if self.current.is_none() {
+            self.open_next()?;
+        }
+        let file = self.current.as_mut().unwrap();
+        for record in batch {
+            file.write_record(record, &self.canonicalizer)?;
+        }
+        file.file.flush()
+    }
+
+    /// Closes the current file, the next batch opens a new one
+    pub fn rotate(&mut self) -> io::Result<()> {
+        match self.current.take() {
+            Some(file) => file.finish(&self.index_formats),
+            None => Ok(()),
+        }
+    }
+
+    fn open_next(&mut self) -> io::Result<()> {
+        let path = self.names.render(self.serial);
+        self.serial += 1;
+        let mut file = OpenFile {
+            // never reuse a name, a file left by an earlier run is kept as it is
+            file: BufWriter::new(OpenOptions::new().write(true).create_new(true).open(&path)?),
+            path: path.clone(),
+            index: Vec::new(),
+            offset: 0,
+            records: 0,
+            opened: Instant::now(),
+        };
+        let mut warcinfo = WetRecord::warcinfo(&file_name(&path), &self.warcinfo_fields);
+        add_digests(&mut warcinfo, self.digest_algorithm);
+        file.write_record(&warcinfo, &self.canonicalizer)?;
+        self.current = Some(file);
+        Ok(())
+    }
+}
+
+fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string())
+}